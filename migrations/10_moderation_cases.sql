@@ -0,0 +1,23 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table ModerationCases (
+    guild_id    bigint      references Guilds (id) on delete cascade,
+    id          integer     not null,
+    action      text        not null,
+    target      bigint      not null,
+    moderator   bigint      not null,
+    reason      text,
+    duration    bigint,
+    anon        boolean     not null,
+    shame       boolean     not null,
+    dm          boolean     not null,
+    created_at  timestamp   not null default now(),
+    constraint cases_idx unique (guild_id, id)
+);
+
+create index cases_target_idx on ModerationCases (guild_id, target);
+
+alter type PermissionType add value 'ManageCases';
@@ -0,0 +1,14 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table ScheduledJobs (
+    id          bigserial   primary key,
+    guild_id    bigint      references Guilds (id) on delete cascade,
+    run_at      timestamp   not null,
+    job         text        not null,
+    attempts    integer     not null default 0
+);
+
+create index jobs_run_idx on ScheduledJobs (run_at);
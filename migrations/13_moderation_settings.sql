@@ -0,0 +1,21 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table ModerationSettings (
+    guild_id        bigint      references Guilds (id) on delete cascade,
+    default_shame   boolean     not null default true,
+    default_dm      boolean     not null default true,
+    default_anon    boolean     not null default false,
+    max_timeout     bigint,
+    require_reason  boolean     not null default false,
+    constraint moderation_settings_idx unique (guild_id)
+);
+
+create table ReasonPresets (
+    guild_id    bigint  references Guilds (id) on delete cascade,
+    name        text    not null,
+    reason      text    not null,
+    constraint reason_presets_idx unique (guild_id, name)
+);
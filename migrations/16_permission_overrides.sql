@@ -0,0 +1,13 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table PermissionOverrides (
+    guild_id    bigint          references Guilds (id) on delete cascade,
+    type        PermissionType  not null,
+    scope       bigint          not null,
+    roles       bigint[]        not null,
+    users       bigint[]        not null,
+    constraint permission_overrides_idx unique (guild_id, type, scope)
+);
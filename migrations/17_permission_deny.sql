@@ -0,0 +1,10 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+alter table Permissions add column deny_roles bigint[] not null default '{}';
+alter table Permissions add column deny_users bigint[] not null default '{}';
+
+alter table PermissionOverrides add column deny_roles bigint[] not null default '{}';
+alter table PermissionOverrides add column deny_users bigint[] not null default '{}';
@@ -0,0 +1,10 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table InstanceAdmins (
+    id          bigint      primary key,
+    added_by    bigint,
+    created_at  timestamp   not null default now()
+);
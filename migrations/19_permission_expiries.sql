@@ -0,0 +1,17 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table PermissionExpiries (
+    id          bigserial       primary key,
+    guild_id    bigint          references Guilds (id) on delete cascade,
+    type        PermissionType  not null,
+    scope       bigint,
+    deny        boolean         not null,
+    role_id     bigint,
+    user_id     bigint,
+    expires_at  timestamp       not null
+);
+
+create index permission_expiries_idx on PermissionExpiries (expires_at);
@@ -0,0 +1,10 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table PermissionSettings (
+    guild_id            bigint      references Guilds (id) on delete cascade,
+    keep_user_grants    boolean     not null default false,
+    constraint permission_settings_idx unique (guild_id)
+);
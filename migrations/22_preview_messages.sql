@@ -0,0 +1,21 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table PreviewSettings (
+    guild_id            bigint      references Guilds (id) on delete cascade,
+    suppress_embeds     boolean     not null default false,
+    constraint preview_settings_idx unique (guild_id)
+);
+
+-- every message a preview was sent as, grouped by the first one, with who triggered it
+create table PreviewMessages (
+    message_id  bigint  primary key,
+    preview_id  bigint  not null,
+    guild_id    bigint  references Guilds (id) on delete cascade,
+    channel_id  bigint  not null,
+    user_id     bigint  not null
+);
+
+create index preview_messages_idx on PreviewMessages (preview_id);
@@ -0,0 +1,18 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+alter table PreviewSettings add column live boolean not null default false;
+
+-- preview and archive messages that follow edits and deletions of their source
+create table LivePreviews (
+    message_id          bigint  primary key,
+    guild_id            bigint  references Guilds (id) on delete cascade,
+    channel_id          bigint  not null,
+    source_channel_id   bigint  not null,
+    source_message_id   bigint  not null,
+    kind                text    not null
+);
+
+create index live_previews_idx on LivePreviews (source_message_id);
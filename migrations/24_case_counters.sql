@@ -0,0 +1,14 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+-- the next case number of each guild, so concurrent cases can't take the same one
+create table CaseCounters (
+    guild_id    bigint      references Guilds (id) on delete cascade,
+    next        integer     not null,
+    constraint case_counters_idx unique (guild_id)
+);
+
+insert into CaseCounters (guild_id, next)
+select guild_id, max(id) + 1 from ModerationCases where guild_id is not null group by guild_id;
@@ -0,0 +1,9 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+-- lets old preview messages be pruned, rows from before this count from now
+alter table PreviewMessages add column created_at timestamp not null default now();
+
+create index preview_messages_age_idx on PreviewMessages (created_at);
@@ -0,0 +1,19 @@
+-- Copyright 2021 Mia
+-- This program is distributed under the terms of the GNU Affero General Public License
+-- You should have received a copy of the license along with this program
+-- If not, see <https://www.gnu.org/licenses/#AGPL>
+
+create table AuditLog (
+    id          bigserial   primary key,
+    guild_id    bigint      references Guilds (id) on delete cascade,
+    actor       bigint      not null,
+    action      text        not null,
+    target      text        not null,
+    before      text,
+    after       text,
+    created_at  timestamp   not null default now()
+);
+
+create index audit_idx on AuditLog (guild_id, id);
+
+alter type PermissionType add value 'ViewAuditLog';
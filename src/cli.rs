@@ -5,12 +5,15 @@
 
 use crate::config::Config;
 use crate::invite_url;
+use crate::migrations;
+use crate::migrations::MigrationState;
 use anyhow::Result;
 use clap::Parser;
 use dialoguer::Input;
 use ron::extensions::Extensions;
 use ron::ser::PrettyConfig;
 use serenity::http::Http;
+use sqlx::postgres::PgPoolOptions;
 use std::fs;
 use std::str::FromStr;
 
@@ -25,6 +28,7 @@ pub enum Subcommand {
     Run,
     Init,
     Invite(InviteOpts),
+    Migrate(MigrateOpts),
 }
 
 #[derive(Parser)]
@@ -33,6 +37,22 @@ pub struct InviteOpts {
     id: Option<u64>,
 }
 
+#[derive(Parser)]
+pub struct MigrateOpts {
+    #[clap(subcommand)]
+    mode: MigrateMode,
+}
+
+#[derive(Parser)]
+pub enum MigrateMode {
+    /// List migrations and whether they have been applied
+    Status,
+    /// Apply pending migrations
+    Run,
+    /// List the migrations that would be applied
+    DryRun,
+}
+
 pub fn init() -> Result<()> {
     let token = Input::new().with_prompt("Bot token").interact_text()?;
 
@@ -61,6 +81,7 @@ pub fn init() -> Result<()> {
         owner_id,
         commands_guild: None,
         github_webhook_secret: None,
+        report_unsupported_messages: false,
     };
 
     fs::write(
@@ -89,3 +110,45 @@ pub fn invite_inner(id: u64) -> Result<()> {
 
     Ok(())
 }
+
+pub async fn migrate(opts: MigrateOpts) -> Result<()> {
+    let config: Config = ron::from_str(&fs::read_to_string("config.ron")?)?;
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&config.database_url)
+        .await?;
+
+    match opts.mode {
+        MigrateMode::Status => {}
+        MigrateMode::Run => migrations::run(&pool).await?,
+        MigrateMode::DryRun => {
+            migrations::ensure_compatible(&pool).await?;
+            let pending = migrations::status(&pool)
+                .await?
+                .into_iter()
+                .filter(|m| !matches!(m.state, MigrationState::Applied))
+                .collect::<Vec<_>>();
+            if pending.is_empty() {
+                println!("Nothing to apply");
+            }
+            for migration in pending {
+                println!("Would apply {}", migration.file_name);
+            }
+            return Ok(());
+        }
+    }
+
+    for migration in migrations::status(&pool).await? {
+        println!(
+            "[{}] {}",
+            match migration.state {
+                MigrationState::Applied => "x",
+                MigrationState::Pending => " ",
+                MigrationState::Dirty => "!",
+            },
+            migration.file_name
+        );
+    }
+
+    Ok(())
+}
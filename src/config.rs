@@ -20,4 +20,7 @@ pub struct Config {
     pub commands_guild: Option<GuildId>,
     #[serde(default)]
     pub github_webhook_secret: Option<String>,
+    // dm the owner when previews run into message types they can't render
+    #[serde(default)]
+    pub report_unsupported_messages: bool,
 }
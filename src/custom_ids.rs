@@ -9,13 +9,19 @@ use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
 pub enum CustomIdType {
-    ListPermissions,
+    PermissionsPage,
+    AuditLog,
+    ConfigImport,
+    PreviewDelete,
 }
 
 impl Display for CustomIdType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
-            Self::ListPermissions => "ListPermissions",
+            Self::PermissionsPage => "PermissionsPage",
+            Self::AuditLog => "AuditLog",
+            Self::ConfigImport => "ConfigImport",
+            Self::PreviewDelete => "PreviewDelete",
         })
     }
 }
@@ -23,7 +29,12 @@ impl Display for CustomIdType {
 impl CustomIdType {
     pub fn from_str(from: &str) -> Result<Self> {
         match from {
-            "ListPermissions" => Ok(Self::ListPermissions),
+            "PermissionsPage" => Ok(Self::PermissionsPage),
+            // select menus sent before the rename still carry the old name
+            "ListPermissions" => Ok(Self::PermissionsPage),
+            "AuditLog" => Ok(Self::AuditLog),
+            "ConfigImport" => Ok(Self::ConfigImport),
+            "PreviewDelete" => Ok(Self::PreviewDelete),
             _ => Err(Error::new(BotError::InvalidRequest(format!(
                 "Invalid CustomID type {}",
                 from
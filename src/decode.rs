@@ -5,12 +5,12 @@
 
 // pretty much a clone of serenity-slash-decode, but using our git version of serenity. there's really no better way of doing this
 
-use serenity::model::channel::PartialChannel;
+use serenity::model::channel::{Attachment, PartialChannel};
 use serenity::model::guild::{PartialMember, Role};
 use serenity::model::id::UserId;
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::interaction::application_command::{
-    CommandData, CommandDataOptionValue,
+    CommandData, CommandDataOption, CommandDataOptionValue,
 };
 use serenity::model::prelude::Mention;
 use serenity::model::user::User;
@@ -126,6 +126,7 @@ impl SlashValue {
             CommandDataOptionValue::User(_, _) => "User".to_string(),
             CommandDataOptionValue::Channel(_) => "Channel".to_string(),
             CommandDataOptionValue::Role(_) => "Role".to_string(),
+            CommandDataOptionValue::Attachment(_) => "Attachment".to_string(),
             _ => "Unknown".to_string(),
         }
     }
@@ -213,6 +214,18 @@ impl SlashValue {
         }
     }
 
+    /// Returns the inner value if it is an `Attachment`
+    pub fn get_attachment(&self) -> Result<Attachment> {
+        match self.expect_some()? {
+            CommandDataOptionValue::Attachment(s) => Ok(s),
+            _ => Err(Error::WrongType {
+                expected: "Attachment".to_string(),
+                found: self.get_type_name(),
+                name: self.name.clone(),
+            }),
+        }
+    }
+
     /// Returns the inner value if it is a `Mentionable`
     #[allow(unused)]
     pub fn get_mentionable(&self) -> Result<Mentionable> {
@@ -299,6 +312,16 @@ impl SlashMap {
         }
     }
 
+    /// If `SlashMap` has value, call `SlashValue::get_attachment()` on it
+    pub fn get_attachment(&self, name: &str) -> Result<Attachment> {
+        match self.0.get(name) {
+            Some(s) => s.get_attachment(),
+            None => Err(Error::MissingValue {
+                name: name.to_string(),
+            }),
+        }
+    }
+
     /// If `SlashMap` has value, call `SlashValue::get_mentionable()` on it
     #[allow(unused)]
     pub fn get_mentionable(&self, name: &str) -> Result<Mentionable> {
@@ -312,20 +335,20 @@ impl SlashMap {
 }
 
 /// For derive macros
+#[allow(dead_code)]
 pub trait FromSlashMap {
     fn from_slash_map(_: SlashMap) -> Result<Self>
     where
         Self: Sized;
 }
 
-/// Processes a `CommandData` and returns the path and arguments
-pub fn process(interaction: &CommandData) -> (String, SlashMap) {
-    // traverse
+// walks down subcommands, returning the path taken and the options at the end
+fn traverse(interaction: &CommandData) -> (String, &Vec<CommandDataOption>) {
     let mut options = &interaction.options;
     let mut path = vec![interaction.name.clone()];
 
     loop {
-        match options.get(0) {
+        match options.first() {
             None => break,
             Some(option) => {
                 if matches!(
@@ -341,6 +364,13 @@ pub fn process(interaction: &CommandData) -> (String, SlashMap) {
         }
     }
 
+    (path.join(" "), options)
+}
+
+/// Processes a `CommandData` and returns the path and arguments
+pub fn process(interaction: &CommandData) -> (String, SlashMap) {
+    let (path, options) = traverse(interaction);
+
     // map data
     let mut map = SlashMap::new();
     for option in options {
@@ -353,5 +383,25 @@ pub fn process(interaction: &CommandData) -> (String, SlashMap) {
         );
     }
 
-    (path.join(" "), map)
+    (path, map)
+}
+
+/// Processes an autocomplete `CommandData` and returns the path and the focused option's name and raw value
+pub fn process_autocomplete(interaction: &CommandData) -> (String, Option<(String, String)>) {
+    let (path, options) = traverse(interaction);
+
+    let focused = options.iter().find(|o| o.focused).map(|option| {
+        (
+            option.name.clone(),
+            match &option.value {
+                Some(value) => value
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| value.to_string()),
+                None => String::new(),
+            },
+        )
+    });
+
+    (path, focused)
 }
@@ -8,7 +8,7 @@ use std::fmt::{Debug, Display, Formatter};
 
 pub enum BotError {
     Generic(String),
-    Internal(u64), // used: 0-13
+    Internal(u64), // used: 0-14
     GuildOnly,
     CacheMissing,
     InvalidRequest(String),
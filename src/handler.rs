@@ -3,29 +3,39 @@
 // You should have received a copy of the license along with this program
 // If not, see <https://www.gnu.org/licenses/#AGPL>
 
-use crate::modules::{PermissionsModule, PreviewsModule, UpdatesModule, UtilsModule};
+use crate::modules::{
+    AuditModule, CasesModule, ConfigModule, PermissionsModule, PreviewsModule, UpdatesModule,
+    UtilsModule,
+};
 use crate::prelude::*;
 use crate::router;
 use log::{error, info};
 use serenity::async_trait;
 use serenity::client::{Context, EventHandler};
 use serenity::model::channel::{GuildChannel, Message};
+use serenity::model::event::MessageUpdateEvent;
 use serenity::model::gateway::{Activity, Ready};
-use serenity::model::guild::Guild;
-use serenity::model::id::{ApplicationId, UserId};
+use serenity::model::guild::{Guild, Member, Role};
+use serenity::model::id::{ApplicationId, ChannelId, GuildId, MessageId, RoleId, UserId};
 use serenity::model::prelude::command::CommandType;
 use serenity::model::prelude::interaction::Interaction;
+use serenity::model::user::User;
 use serenity::utils::Color;
 use sqlx::{Pool, Postgres};
 
 pub struct Handler {
     pub pool: Pool<Postgres>,
+    // only passed through the permission macros, which ignore it
+    #[allow(dead_code)]
     pub application_id: ApplicationId,
     pub owner_id: UserId,
     pub updates: Arc<UpdatesModule>,
     pub permissions: Arc<PermissionsModule>,
     pub previews: Arc<PreviewsModule>,
     pub utils: Arc<UtilsModule>,
+    pub audit: Arc<AuditModule>,
+    pub cases: Arc<CasesModule>,
+    pub config: Arc<ConfigModule>,
 }
 
 macro_rules! handler_log {
@@ -50,6 +60,7 @@ impl EventHandler for Handler {
         let b_ctx = BotContext::build(ctx, self.pool.clone());
         tokio::join! {
             pass_event!("Previews", &self.previews, PreviewsModule::channel_delete, &b_ctx, channel),
+            pass_event!("Utils", &self.utils, UtilsModule::channel_delete, &b_ctx, channel),
         };
     }
 
@@ -64,6 +75,33 @@ impl EventHandler for Handler {
         );
         tokio::join! {
             pass_event!("Previews", &self.previews, PreviewsModule::guild_data, &b_ctx, &guild),
+            pass_event!("Permissions", &self.permissions, PermissionsModule::guild_data, &b_ctx, &guild),
+        };
+    }
+
+    async fn guild_role_delete(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        removed_role_id: RoleId,
+        _: Option<Role>,
+    ) {
+        let b_ctx = BotContext::build(ctx, self.pool.clone());
+        tokio::join! {
+            pass_event!("Permissions", &self.permissions, PermissionsModule::role_delete, &b_ctx, &guild_id, &removed_role_id),
+        };
+    }
+
+    async fn guild_member_removal(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        user: User,
+        _: Option<Member>,
+    ) {
+        let b_ctx = BotContext::build(ctx, self.pool.clone());
+        tokio::join! {
+            pass_event!("Permissions", &self.permissions, PermissionsModule::member_removal, &b_ctx, &guild_id, &user),
         };
     }
 
@@ -73,6 +111,50 @@ impl EventHandler for Handler {
             pass_event!("Previews", &self.previews, PreviewsModule::message, &b_ctx, &message),
         };
     }
+
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _: Option<Message>,
+        _: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        let b_ctx = BotContext::build(ctx, self.pool.clone());
+        tokio::join! {
+            pass_event!("Previews", &self.previews, PreviewsModule::message_update, &b_ctx, &event),
+        };
+    }
+
+    async fn message_delete(
+        &self,
+        ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _: Option<GuildId>,
+    ) {
+        let b_ctx = BotContext::build(ctx, self.pool.clone());
+        tokio::join! {
+            pass_event!("Previews", &self.previews, PreviewsModule::message_delete, &b_ctx, &channel_id, &deleted_message_id),
+        };
+    }
+
+    async fn message_delete_bulk(
+        &self,
+        ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_ids: Vec<MessageId>,
+        _: Option<GuildId>,
+    ) {
+        let b_ctx = BotContext::build(ctx, self.pool.clone());
+        for deleted_message_id in deleted_message_ids {
+            handler_log!(
+                "Previews",
+                self.previews
+                    .message_delete(&b_ctx, &channel_id, &deleted_message_id)
+                    .await
+            );
+        }
+    }
     async fn ready(&self, ctx: Context, _: Ready) {
         info!("received ready event");
         ctx.shard
@@ -134,6 +216,10 @@ impl EventHandler for Handler {
                     ),
                 }
             }
+            Interaction::Autocomplete(autocomplete) => handler_log!(
+                "Autocomplete",
+                router::autocomplete_router(self, &b_ctx, &autocomplete).await
+            ),
             _ => {}
         }
     }
@@ -14,6 +14,7 @@ mod error;
 mod handler;
 mod logging;
 mod macros;
+mod migrations;
 mod modules;
 mod router;
 mod sql;
@@ -81,6 +82,7 @@ async fn bootstrap() -> Result<()> {
         Subcommand::Run => start().await,
         Subcommand::Init => cli::init(),
         Subcommand::Invite(opts) => cli::invite(opts).await,
+        Subcommand::Migrate(opts) => cli::migrate(opts).await,
     }
 }
 
@@ -102,7 +104,8 @@ async fn start() -> Result<()> {
     info!("connecting to database");
     let pool = PgPoolOptions::new().connect(&config.database_url).await?;
 
-    //    sqlx::migrate!().run(&pool).await?;
+    info!("running migrations");
+    migrations::run(&pool).await?;
 
     info!("initializing handler");
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
@@ -113,8 +116,19 @@ async fn start() -> Result<()> {
         config.client_id,
     ));
     let permissions_module = Arc::new(modules::PermissionsModule::new(pool.clone()));
-    let previews_module = Arc::new(modules::PreviewsModule::new()?);
-    let utils_module = Arc::new(modules::UtilsModule::new());
+    let previews_module = Arc::new(modules::PreviewsModule::new(
+        config
+            .report_unsupported_messages
+            .then_some(UserId(config.owner_id)),
+    )?);
+    let utils_module = Arc::new(modules::UtilsModule::new()?);
+    let audit_module = Arc::new(modules::AuditModule::new());
+    let cases_module = Arc::new(modules::CasesModule::new());
+    let config_module = Arc::new(modules::ConfigModule::new(
+        permissions_module.clone(),
+        previews_module.clone(),
+        utils_module.clone(),
+    ));
 
     let handler = Handler {
         pool: pool.clone(),
@@ -123,14 +137,23 @@ async fn start() -> Result<()> {
         updates: updates_module.clone(),
         permissions: permissions_module.clone(),
         previews: previews_module.clone(),
-        utils: utils_module,
+        utils: utils_module.clone(),
+        audit: audit_module,
+        cases: cases_module,
+        config: config_module,
     };
 
     info!("initializing modules");
     let (task_tx, _) = broadcast::channel(0x400);
-    modules::PermissionsModule::initialize(permissions_module.clone(), task_tx.subscribe()).await?;
+    modules::PermissionsModule::initialize(
+        permissions_module.clone(),
+        task_tx.subscribe(),
+        UserId(config.owner_id),
+    )
+    .await?;
     modules::PreviewsModule::initialize(previews_module.clone(), task_tx.subscribe(), &pool)
         .await?;
+    modules::UtilsModule::initialize(utils_module.clone(), task_tx.subscribe(), &pool).await?;
 
     info!("initializing client");
     let mut client = Client::builder(
@@ -184,6 +207,34 @@ async fn start() -> Result<()> {
         .await;
     });
 
+    let task_ctx_clone = task_ctx.clone();
+    let permissions_clone = permissions_module.clone();
+    tokio::spawn(async move {
+        background_task(
+            "Permission Expiry",
+            |ctx| tasks::permission_expiry(ctx.clone(), permissions_clone.clone()),
+            task_ctx_clone,
+            Duration::minutes(1),
+        )
+        .await;
+    });
+
+    let task_ctx_clone = task_ctx.clone();
+    let previews_clone = previews_module.clone();
+    tokio::spawn(async move {
+        background_task(
+            "Preview Cleanup",
+            |ctx| tasks::preview_cleanup(ctx.clone(), previews_clone.clone()),
+            task_ctx_clone,
+            Duration::days(1),
+        )
+        .await;
+    });
+
+    tokio::spawn(async move {
+        tasks::job_worker(task_ctx).await;
+    });
+
     let shutdown_tx_clone = shutdown_tx.clone();
     tokio::spawn(async move {
         CtrlC::new().expect("Error registering CtrlC handler").await;
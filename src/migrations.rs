@@ -0,0 +1,140 @@
+// Copyright 2021 Mia
+// This program is distributed under the terms of the GNU Affero General Public License
+// You should have received a copy of the license along with this program
+// If not, see <https://www.gnu.org/licenses/#AGPL>
+
+// embedded sql migrations and the checks around running them
+
+use crate::prelude::*;
+use anyhow::{Error, Result};
+use sqlx::migrate::Migrate;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
+
+// deployments from before migrations were embedded applied everything up to this one by hand
+const LEGACY_LATEST: i64 = 8;
+
+pub enum MigrationState {
+    Applied,
+    Pending,
+    // migration was started but never finished
+    Dirty,
+}
+
+pub struct MigrationStatus {
+    pub file_name: String,
+    pub state: MigrationState,
+}
+
+/// Whether the schema was set up by hand, without any migration history
+async fn legacy_schema(pool: &PgPool) -> Result<bool> {
+    Ok(sqlx::query(
+        "select to_regclass('guilds') is not null and to_regclass('_sqlx_migrations') is null as legacy",
+    )
+    .map(|row: PgRow| row.get::<bool, _>("legacy"))
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Records the hand-applied migrations of a legacy schema, so they aren't run a second time
+async fn baseline_legacy(pool: &PgPool) -> Result<()> {
+    if !legacy_schema(pool).await? {
+        return Ok(());
+    }
+    info!(
+        "found a schema without migration history, marking migrations up to {} as applied",
+        LEGACY_LATEST
+    );
+
+    let mut tx = pool.begin().await?;
+    tx.ensure_migrations_table().await?;
+    for migration in MIGRATOR.iter().filter(|m| m.version <= LEGACY_LATEST) {
+        sqlx::query(
+            "insert into _sqlx_migrations (version, description, success, checksum, execution_time) values ($1, $2, true, $3, 0)",
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Versions recorded in the database, mapped to whether they finished successfully
+async fn applied_versions(pool: &PgPool) -> Result<HashMap<i64, bool>> {
+    // legacy schemas count as migrated up to where they were set up by hand
+    if legacy_schema(pool).await? {
+        return Ok((0..=LEGACY_LATEST).map(|version| (version, true)).collect());
+    }
+
+    // the table doesn't exist until the first migration run
+    let exists = sqlx::query("select to_regclass('_sqlx_migrations') is not null as exists")
+        .map(|row: PgRow| row.get::<bool, _>("exists"))
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Ok(HashMap::new());
+    }
+
+    Ok(sqlx::query("select version, success from _sqlx_migrations")
+        .map(|row: PgRow| (row.get::<i64, _>("version"), row.get::<bool, _>("success")))
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect())
+}
+
+fn latest_known() -> i64 {
+    MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let applied = applied_versions(pool).await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .map(|migration| MigrationStatus {
+            // sqlx turns underscores in the description into spaces, so undo that
+            file_name: format!(
+                "{}_{}.sql",
+                migration.version,
+                migration.description.replace(' ', "_")
+            ),
+            state: match applied.get(&migration.version) {
+                Some(true) => MigrationState::Applied,
+                Some(false) => MigrationState::Dirty,
+                None => MigrationState::Pending,
+            },
+        })
+        .collect())
+}
+
+/// Errors if the database has migrations applied that this build doesn't know about
+pub async fn ensure_compatible(pool: &PgPool) -> Result<()> {
+    let latest = latest_known();
+    if let Some(version) = applied_versions(pool)
+        .await?
+        .into_keys()
+        .filter(|version| *version > latest)
+        .max()
+    {
+        return Err(Error::msg(format!(
+            "database is at migration {} but this build only knows up to {}, refusing to continue",
+            version, latest
+        )));
+    }
+
+    Ok(())
+}
+
+pub async fn run(pool: &PgPool) -> Result<()> {
+    ensure_compatible(pool).await?;
+    baseline_legacy(pool).await?;
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
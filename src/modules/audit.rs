@@ -0,0 +1,238 @@
+// Copyright 2021 Mia
+// This program is distributed under the terms of the GNU Affero General Public License
+// You should have received a copy of the license along with this program
+// If not, see <https://www.gnu.org/licenses/#AGPL>
+
+use crate::custom_ids::CustomIdType;
+use crate::prelude::*;
+use crate::utils::{create_page_buttons, defer_command, defer_component, BotContext, SqlId};
+use anyhow::{Error, Result};
+use chrono::NaiveDateTime;
+use serenity::builder::CreateEmbed;
+use serenity::model::id::{GuildId, UserId};
+use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::prelude::Mentionable;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Row};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const PAGE_SIZE: i64 = 10;
+
+macro_rules! impl_audit_action {
+    ($($enum: ident, $value: expr, $display: expr),+) => {
+        #[derive(Copy, Clone, Debug)]
+        pub enum AuditAction {
+            $($enum),+
+        }
+
+        impl AuditAction {
+            pub fn as_value(&self) -> &'static str {
+                match self {
+                    $(AuditAction::$enum => $value),+
+                }
+            }
+
+            pub fn as_display(&self) -> &'static str {
+                match self {
+                    $(AuditAction::$enum => $display),+
+                }
+            }
+
+            pub fn from_string(from: &str) -> Result<Self> {
+                match from {
+                    $($value => Ok(AuditAction::$enum)),+,
+                    _ => Err(Error::new(BotError::InvalidRequest(format!("Invalid audit action {}", from))))
+                }
+            }
+        }
+    }
+}
+
+impl_audit_action!(
+    Permission,
+    "Permission",
+    "Permission changed",
+    PreviewChannelAdd,
+    "PreviewChannelAdd",
+    "Preview channel added",
+    PreviewChannelRemove,
+    "PreviewChannelRemove",
+    "Preview channel removed",
+    ArchiveChannel,
+    "ArchiveChannel",
+    "Archive channel changed",
+    ModerationSetting,
+    "ModerationSetting",
+    "Moderation setting changed",
+    ReasonPresetAdd,
+    "ReasonPresetAdd",
+    "Reason preset added",
+    ReasonPresetRemove,
+    "ReasonPresetRemove",
+    "Reason preset removed",
+    ModerationLogChannel,
+    "ModerationLogChannel",
+    "Moderation log channel changed",
+    ConfigImport,
+    "ConfigImport",
+    "Configuration imported",
+    PermissionExpired,
+    "PermissionExpired",
+    "Permission grant expired",
+    PermissionPruned,
+    "PermissionPruned",
+    "Deleted roles or departed users removed",
+    PermissionSetting,
+    "PermissionSetting",
+    "Permission setting changed",
+    PreviewSetting,
+    "PreviewSetting",
+    "Preview setting changed"
+);
+
+pub struct AuditEntry {
+    pub guild_id: GuildId,
+    pub actor: UserId,
+    pub action: AuditAction,
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl AuditEntry {
+    pub async fn record(self, pool: &PgPool) -> Result<()> {
+        self.record_in(&mut *pool.acquire().await?).await
+    }
+
+    /// Same as `record`, as part of a transaction
+    pub async fn record_in(self, conn: &mut PgConnection) -> Result<()> {
+        sqlx::query(
+            "insert into AuditLog (guild_id, actor, action, target, before, after) values ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(SqlId(self.guild_id))
+        .bind(SqlId(self.actor))
+        .bind(self.action.as_value())
+        .bind(&self.target)
+        .bind(&self.before)
+        .bind(&self.after)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+}
+
+pub struct AuditModule {}
+
+impl AuditModule {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    // returns the page embed and whether there is a page after this one
+    async fn build_page(
+        pool: &PgPool,
+        guild_id: GuildId,
+        page: u64,
+    ) -> Result<(CreateEmbed, bool)> {
+        // fetch one extra row to find out if there's a next page
+        let rows = sqlx::query(
+            "select id, actor, action, target, before, after, created_at from AuditLog \
+             where guild_id = $1 order by id desc limit $2 offset $3",
+        )
+        .bind(SqlId(guild_id))
+        .bind(PAGE_SIZE + 1)
+        .bind(page as i64 * PAGE_SIZE)
+        .map(|row: PgRow| -> Result<String> {
+            let before = row.get::<Option<String>, _>("before");
+            let after = row.get::<Option<String>, _>("after");
+            Ok(format!(
+                "`#{}` <t:{}:f> {} **{}** {}{}",
+                row.get::<i64, _>("id"),
+                row.get::<NaiveDateTime, _>("created_at")
+                    .and_utc()
+                    .timestamp(),
+                row.get::<SqlId<UserId>, _>("actor").0.mention(),
+                AuditAction::from_string(row.get("action"))?.as_display(),
+                row.get::<String, _>("target"),
+                match (before, after) {
+                    (None, None) => String::new(),
+                    (before, after) => format!(
+                        "\n{} → {}",
+                        before.as_deref().unwrap_or("*none*"),
+                        after.as_deref().unwrap_or("*none*")
+                    ),
+                }
+            ))
+        })
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect::<Result<Vec<String>>>()?;
+
+        let has_next = rows.len() as i64 > PAGE_SIZE;
+
+        let mut embed = CreateEmbed::default();
+        embed
+            .title("Audit Log")
+            .footer(|f| f.text(format!("Page {}", page + 1)));
+        if rows.is_empty() {
+            embed.description("No entries");
+        } else {
+            embed.description(
+                rows.into_iter()
+                    .take(PAGE_SIZE as usize)
+                    .collect::<Vec<String>>()
+                    .join("\n\n"),
+            );
+        }
+
+        Ok((embed, has_next))
+    }
+
+    pub async fn audit_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+
+        let (embed, has_next) = Self::build_page(&ctx.pool, guild_id, 0).await?;
+
+        interaction
+            .create_followup_message(&ctx.http, |m| {
+                m.add_embed(embed)
+                    .components(|c| create_page_buttons(c, &CustomIdType::AuditLog, 0, has_next))
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn audit_component(
+        &self,
+        ctx: &BotContext,
+        interaction: &MessageComponentInteraction,
+        args: HashMap<String, String>,
+    ) -> Result<()> {
+        defer_component(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let page = args
+            .get("page")
+            .and_then(|p| u64::from_str(p).ok())
+            .unwrap_or(0);
+
+        let (embed, has_next) = Self::build_page(&ctx.pool, guild_id, page).await?;
+
+        interaction
+            .edit_original_interaction_response(&ctx.http, |m| {
+                m.set_embed(embed)
+                    .components(|c| create_page_buttons(c, &CustomIdType::AuditLog, page, has_next))
+            })
+            .await?;
+
+        Ok(())
+    }
+}
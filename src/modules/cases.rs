@@ -0,0 +1,375 @@
+// Copyright 2021 Mia
+// This program is distributed under the terms of the GNU Affero General Public License
+// You should have received a copy of the license along with this program
+// If not, see <https://www.gnu.org/licenses/#AGPL>
+
+use crate::decode::SlashMap;
+use crate::prelude::*;
+use crate::utils::{defer_command, display_duration, BotContext, FollowupBuilder, SqlId};
+use anyhow::{Error, Result};
+use chrono::NaiveDateTime;
+use serenity::builder::CreateEmbed;
+use serenity::model::id::{GuildId, UserId};
+use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::prelude::Mentionable;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+
+const CASE_LIST_LIMIT: i64 = 20;
+
+macro_rules! impl_case_action {
+    ($($enum: ident, $value: expr, $display: expr),+) => {
+        #[allow(dead_code)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        pub enum CaseAction {
+            $($enum),+
+        }
+
+        #[allow(dead_code)]
+        impl CaseAction {
+            pub fn as_value(&self) -> &'static str {
+                match self {
+                    $(CaseAction::$enum => $value),+
+                }
+            }
+
+            pub fn as_display(&self) -> &'static str {
+                match self {
+                    $(CaseAction::$enum => $display),+
+                }
+            }
+
+            pub fn from_string(from: &str) -> Result<Self> {
+                match from {
+                    $($value => Ok(CaseAction::$enum)),+,
+                    _ => Err(Error::new(BotError::InvalidRequest(format!("Invalid case action {}", from))))
+                }
+            }
+        }
+    }
+}
+
+impl_case_action!(
+    Timeout,
+    "Timeout",
+    "Timeout",
+    Untimeout,
+    "Untimeout",
+    "Untimeout",
+    Kick,
+    "Kick",
+    "Kick",
+    Ban,
+    "Ban",
+    "Ban",
+    Softban,
+    "Softban",
+    "Softban",
+    Unban,
+    "Unban",
+    "Unban"
+);
+
+/// What happened to the DM sent along with a sanction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DmStatus {
+    Delivered,
+    // discord answers the same way whether the user blocked us or closed their DMs
+    Closed,
+    Failed,
+}
+
+impl DmStatus {
+    pub fn as_value(&self) -> &'static str {
+        match self {
+            DmStatus::Delivered => "Delivered",
+            DmStatus::Closed => "Closed",
+            DmStatus::Failed => "Failed",
+        }
+    }
+
+    pub fn as_display(&self) -> &'static str {
+        match self {
+            DmStatus::Delivered => "Delivered",
+            DmStatus::Closed => "Blocked the bot or has DMs closed",
+            DmStatus::Failed => "Failed for an unknown reason",
+        }
+    }
+
+    pub fn from_string(from: &str) -> Result<Self> {
+        match from {
+            "Delivered" => Ok(DmStatus::Delivered),
+            "Closed" => Ok(DmStatus::Closed),
+            "Failed" => Ok(DmStatus::Failed),
+            _ => Err(Error::new(BotError::InvalidRequest(format!(
+                "Invalid dm status {}",
+                from
+            )))),
+        }
+    }
+}
+
+pub struct NewCase {
+    pub guild_id: GuildId,
+    pub action: CaseAction,
+    pub target: UserId,
+    pub moderator: UserId,
+    pub reason: Option<String>,
+    pub duration: Option<Duration>,
+    pub anon: bool,
+    pub shame: bool,
+    pub dm: bool,
+    pub dm_status: Option<DmStatus>,
+}
+
+impl NewCase {
+    /// Stores the case, returning its guild-local number
+    pub async fn record(&self, pool: &PgPool) -> Result<i32> {
+        Ok(sqlx::query(
+            "with counter as (\
+                 insert into CaseCounters (guild_id, next) values ($1, 2) \
+                 on conflict on constraint case_counters_idx do update set next = CaseCounters.next + 1 \
+                 returning next - 1 as id\
+             ) \
+             insert into ModerationCases (guild_id, id, action, target, moderator, reason, duration, anon, shame, dm, dm_status) \
+             values ($1, (select id from counter), $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+             returning id",
+        )
+        .bind(SqlId(self.guild_id))
+        .bind(self.action.as_value())
+        .bind(SqlId(self.target))
+        .bind(SqlId(self.moderator))
+        .bind(&self.reason)
+        .bind(self.duration.map(|d| d.as_secs() as i64))
+        .bind(self.anon)
+        .bind(self.shame)
+        .bind(self.dm)
+        .bind(self.dm_status.map(|s| s.as_value()))
+        .map(|row: PgRow| row.get::<i32, _>("id"))
+        .fetch_one(pool)
+        .await?)
+    }
+}
+
+pub struct Case {
+    pub id: i32,
+    pub action: CaseAction,
+    pub target: UserId,
+    pub moderator: UserId,
+    pub reason: Option<String>,
+    pub duration: Option<Duration>,
+    pub anon: bool,
+    pub shame: bool,
+    pub dm: bool,
+    pub dm_status: Option<DmStatus>,
+    pub created_at: NaiveDateTime,
+}
+
+impl Case {
+    fn from_row(row: PgRow) -> Result<Self> {
+        Ok(Self {
+            id: row.get("id"),
+            action: CaseAction::from_string(row.get("action"))?,
+            target: row.get::<SqlId<UserId>, _>("target").0,
+            moderator: row.get::<SqlId<UserId>, _>("moderator").0,
+            reason: row.get("reason"),
+            duration: row
+                .get::<Option<i64>, _>("duration")
+                .map(|d| Duration::from_secs(d as u64)),
+            anon: row.get("anon"),
+            shame: row.get("shame"),
+            dm: row.get("dm"),
+            dm_status: row
+                .get::<Option<String>, _>("dm_status")
+                .map(|s| DmStatus::from_string(&s))
+                .transpose()?,
+            created_at: row.get("created_at"),
+        })
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "`#{}` <t:{}:d> **{}**{} by {} - {}",
+            self.id,
+            self.created_at.and_utc().timestamp(),
+            self.action.as_display(),
+            match self.duration {
+                Some(duration) => format!(" ({})", display_duration(&duration)),
+                None => "".to_string(),
+            },
+            self.moderator.mention(),
+            self.reason.as_deref().unwrap_or("*No reason*")
+        )
+    }
+
+    fn embed(&self) -> CreateEmbed {
+        let mut embed = CreateEmbed::default();
+        embed
+            .title(format!("Case #{}", self.id))
+            .field("Action", self.action.as_display(), true)
+            .field("Target", self.target.mention(), true)
+            .field("Moderator", self.moderator.mention(), true)
+            .field(
+                "Reason",
+                self.reason.as_deref().unwrap_or("*No reason*"),
+                false,
+            );
+        if let Some(duration) = &self.duration {
+            embed.field("Duration", display_duration(duration), true);
+        }
+        if let Some(dm_status) = &self.dm_status {
+            embed.field("DM", dm_status.as_display(), true);
+        }
+        embed
+            .field(
+                "Flags",
+                format!(
+                    "Anon: {}\nShame: {}\nDM: {}",
+                    self.anon, self.shame, self.dm
+                ),
+                true,
+            )
+            .field(
+                "Created",
+                format!("<t:{}:f>", self.created_at.and_utc().timestamp()),
+                true,
+            );
+        embed
+    }
+}
+
+pub struct CasesModule {}
+
+impl CasesModule {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    async fn get_case(pool: &PgPool, guild_id: GuildId, id: i64) -> Result<Case> {
+        let not_found = || Error::new(BotError::NotFound(format!("Case #{}", id)));
+        // case numbers are stored as integers, anything bigger can't exist
+        let id32 = i32::try_from(id).map_err(|_| not_found())?;
+        sqlx::query(
+            "select id, action, target, moderator, reason, duration, anon, shame, dm, dm_status, created_at \
+             from ModerationCases where guild_id = $1 and id = $2",
+        )
+        .bind(SqlId(guild_id))
+        .bind(id32)
+        .fetch_optional(pool)
+        .await?
+        .map(Case::from_row)
+        .ok_or_else(not_found)?
+    }
+
+    pub async fn cases_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let user = args.get_user("user")?;
+        let user = user.get_user();
+
+        let total = sqlx::query(
+            "select count(*) as total from ModerationCases where guild_id = $1 and target = $2",
+        )
+        .bind(SqlId(guild_id))
+        .bind(SqlId(user.id))
+        .map(|row: PgRow| row.get::<i64, _>("total"))
+        .fetch_one(&ctx.pool)
+        .await?;
+
+        let cases = sqlx::query(
+            "select id, action, target, moderator, reason, duration, anon, shame, dm, dm_status, created_at \
+             from ModerationCases where guild_id = $1 and target = $2 order by id desc limit $3",
+        )
+        .bind(SqlId(guild_id))
+        .bind(SqlId(user.id))
+        .bind(CASE_LIST_LIMIT)
+        .fetch_all(&ctx.pool)
+        .await?
+        .into_iter()
+        .map(Case::from_row)
+        .collect::<Result<Vec<Case>>>()?;
+
+        if cases.is_empty() {
+            return FollowupBuilder::new()
+                .description(format!("{} has no cases", user.mention()))
+                .build_command_followup(&ctx, interaction)
+                .await;
+        }
+
+        interaction
+            .create_followup_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title(format!("Cases for {}", user.tag()))
+                        .description(
+                            cases
+                                .iter()
+                                .map(|c| c.summary())
+                                .collect::<Vec<String>>()
+                                .join("\n"),
+                        )
+                        .footer(|f| f.text(format!("Showing {} of {} cases", cases.len(), total)))
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn case_view(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let case = Self::get_case(&ctx.pool, guild_id, args.get_integer("id")?).await?;
+
+        interaction
+            .create_followup_message(&ctx, |m| m.add_embed(case.embed()))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn case_edit_reason(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let id = args.get_integer("id")?;
+        let reason = args.get_string("reason")?;
+
+        let updated = match i32::try_from(id) {
+            Ok(id) => sqlx::query(
+                "update ModerationCases set reason = $3 where guild_id = $1 and id = $2",
+            )
+            .bind(SqlId(guild_id))
+            .bind(id)
+            .bind(&reason)
+            .execute(&ctx.pool)
+            .await?
+            .rows_affected(),
+            // case numbers are stored as integers, anything bigger can't exist
+            Err(_) => 0,
+        };
+
+        if updated == 0 {
+            return Err(Error::new(BotError::NotFound(format!("Case #{}", id))));
+        }
+
+        FollowupBuilder::new()
+            .description(format!("Updated reason for case #{}", id))
+            .build_command_followup(&ctx, interaction)
+            .await
+    }
+}
@@ -0,0 +1,813 @@
+// Copyright 2021 Mia
+// This program is distributed under the terms of the GNU Affero General Public License
+// You should have received a copy of the license along with this program
+// If not, see <https://www.gnu.org/licenses/#AGPL>
+
+use crate::custom_ids::{build_custom_id, CustomIdType};
+use crate::decode::SlashMap;
+use crate::modules::audit::{AuditAction, AuditEntry};
+use crate::modules::{PermissionType, PermissionsModule, PreviewsModule, UtilsModule};
+use crate::prelude::*;
+use crate::utils::{defer_command, defer_component, display_duration, FollowupBuilder};
+use anyhow::{Error, Result};
+use serenity::model::channel::{AttachmentType, Channel};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::prelude::Mentionable;
+use serenity::utils::Color;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+// bump this whenever the file format changes in a way old files can't be read as
+const CONFIG_VERSION: u32 = 1;
+// a full configuration is a few kilobytes, anything bigger is not one of ours
+const MAX_CONFIG_SIZE: u64 = 1024 * 1024;
+// how long an import waits for its confirmation
+const IMPORT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+// discord caps embed field values at 1024 characters
+const MAX_FIELD_LENGTH: usize = 1024;
+// discord allows at most 25 fields per embed
+const MAX_DIFF_FIELDS: usize = 25;
+
+fn mentions<T: Mentionable>(items: &[T]) -> String {
+    if items.is_empty() {
+        s!("None")
+    } else {
+        items
+            .iter()
+            .map(|x| x.mention().to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+fn optional_mention<T: Mentionable>(item: &Option<T>) -> String {
+    match item {
+        Some(item) => item.mention().to_string(),
+        None => s!("None"),
+    }
+}
+
+/// A guild's full bot configuration, as stored in export files
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConfigFile {
+    pub version: u32,
+    pub permissions: BTreeMap<String, PermissionSection>,
+    #[serde(default)]
+    pub keep_user_grants: bool,
+    pub previews: PreviewSection,
+    pub moderation: ModerationSection,
+    // lets another server match the ids by name
+    #[serde(default)]
+    pub names: NameSection,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermissionSection {
+    pub discord: u64,
+    pub roles: Vec<RoleId>,
+    pub users: Vec<UserId>,
+    #[serde(default)]
+    pub deny_roles: Vec<RoleId>,
+    #[serde(default)]
+    pub deny_users: Vec<UserId>,
+    #[serde(default)]
+    pub scopes: Vec<ScopeSection>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScopeSection {
+    pub channel: ChannelId,
+    #[serde(default)]
+    pub roles: Vec<RoleId>,
+    #[serde(default)]
+    pub users: Vec<UserId>,
+    #[serde(default)]
+    pub deny_roles: Vec<RoleId>,
+    #[serde(default)]
+    pub deny_users: Vec<UserId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PreviewSection {
+    pub channels: Vec<ChannelId>,
+    #[serde(default)]
+    pub webhook_channels: Vec<ChannelId>,
+    pub archive: Option<ChannelId>,
+    #[serde(default)]
+    pub suppress_embeds: bool,
+    #[serde(default)]
+    pub live: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModerationSection {
+    pub shame: bool,
+    pub dm: bool,
+    pub anon: bool,
+    // in seconds
+    pub max_timeout: Option<u64>,
+    pub require_reason: bool,
+    pub log_channel: Option<ChannelId>,
+    pub presets: Vec<PresetSection>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PresetSection {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct NameSection {
+    #[serde(default)]
+    pub roles: Vec<NamedId<RoleId>>,
+    #[serde(default)]
+    pub channels: Vec<NamedId<ChannelId>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NamedId<T> {
+    pub id: T,
+    pub name: String,
+}
+
+fn channel_name(channel: &Channel) -> Option<&str> {
+    match channel {
+        Channel::Guild(channel) => Some(&channel.name),
+        Channel::Category(category) => Some(&category.name),
+        _ => None,
+    }
+}
+
+// ids that don't exist, matched to the only item of the server with the same name
+fn match_names<T: Copy + Eq + Hash>(
+    missing: Vec<T>,
+    names: &[NamedId<T>],
+    existing: &HashMap<T, String>,
+) -> HashMap<T, T> {
+    let mut by_name = HashMap::<&str, Vec<T>>::new();
+    for (id, name) in existing {
+        by_name.entry(name).or_default().push(*id);
+    }
+    missing
+        .into_iter()
+        .filter_map(|id| {
+            let named = names.iter().find(|named| named.id == id)?;
+            match by_name.get(named.name.as_str())?.as_slice() {
+                [matched] => Some((id, *matched)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// lists ids as `name` where the file knows it, `id` otherwise
+fn describe_missing<T: Copy + Eq + Display>(missing: &[T], names: &[NamedId<T>]) -> String {
+    missing
+        .iter()
+        .map(|id| match names.iter().find(|named| named.id == *id) {
+            Some(named) => format!("`{}` (`{}`)", named.name, id),
+            None => format!("`{}`", id),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl PermissionSection {
+    fn describe(&self) -> String {
+        let mut parts = vec![
+            format!("Bits `{}`", self.discord),
+            format!("Roles {}", mentions(&self.roles)),
+            format!("Users {}", mentions(&self.users)),
+        ];
+        if !self.deny_roles.is_empty() {
+            parts.push(format!("Denied roles {}", mentions(&self.deny_roles)));
+        }
+        if !self.deny_users.is_empty() {
+            parts.push(format!("Denied users {}", mentions(&self.deny_users)));
+        }
+        for scope in &self.scopes {
+            let mut rules = Vec::new();
+            if !scope.roles.is_empty() {
+                rules.push(format!("roles {}", mentions(&scope.roles)));
+            }
+            if !scope.users.is_empty() {
+                rules.push(format!("users {}", mentions(&scope.users)));
+            }
+            if !scope.deny_roles.is_empty() {
+                rules.push(format!("denied roles {}", mentions(&scope.deny_roles)));
+            }
+            if !scope.deny_users.is_empty() {
+                rules.push(format!("denied users {}", mentions(&scope.deny_users)));
+            }
+            parts.push(format!(
+                "In {}: {}",
+                scope.channel.mention(),
+                rules.join(", ")
+            ));
+        }
+        parts.join("\n")
+    }
+}
+
+impl ConfigFile {
+    // (label, summary) for every part of the configuration, in display order
+    fn sections(&self) -> Vec<(String, String)> {
+        let mut sections = self
+            .permissions
+            .iter()
+            .map(|(ty, section)| {
+                (
+                    format!(
+                        "Permission {}",
+                        PermissionType::from_string(ty)
+                            .map(|ty| ty.as_display())
+                            .unwrap_or(ty)
+                    ),
+                    section.describe(),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+
+        sections.push((s!("Keep user grants"), self.keep_user_grants.to_string()));
+        sections.push((s!("Preview channels"), mentions(&self.previews.channels)));
+        sections.push((
+            s!("Webhook preview channels"),
+            mentions(&self.previews.webhook_channels),
+        ));
+        sections.push((
+            s!("Archive channel"),
+            optional_mention(&self.previews.archive),
+        ));
+        sections.push((
+            s!("Suppress link embeds"),
+            self.previews.suppress_embeds.to_string(),
+        ));
+        sections.push((s!("Live previews"), self.previews.live.to_string()));
+
+        let moderation = &self.moderation;
+        sections.push((
+            s!("Moderation defaults"),
+            format!(
+                "Shame {}, DM {}, Anon {}, Require reason {}, Max timeout {}",
+                moderation.shame,
+                moderation.dm,
+                moderation.anon,
+                moderation.require_reason,
+                match moderation.max_timeout {
+                    Some(seconds) => display_duration(&Duration::from_secs(seconds)),
+                    None => s!("none"),
+                }
+            ),
+        ));
+        sections.push((
+            s!("Moderation log channel"),
+            optional_mention(&moderation.log_channel),
+        ));
+        sections.push((
+            s!("Reason presets"),
+            if moderation.presets.is_empty() {
+                s!("None")
+            } else {
+                moderation
+                    .presets
+                    .iter()
+                    .map(|preset| format!("`{}`: {}", preset.name, preset.reason))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            },
+        ));
+
+        sections
+    }
+
+    /// Every section that differs between `self` and `other`, as (label, before, after)
+    fn diff(&self, other: &ConfigFile) -> Vec<(String, String, String)> {
+        let after = other
+            .sections()
+            .into_iter()
+            .collect::<HashMap<String, String>>();
+        self.sections()
+            .into_iter()
+            .filter_map(|(label, before)| {
+                let after = after.get(&label)?;
+                if &before == after {
+                    None
+                } else {
+                    Some((label, before, after.clone()))
+                }
+            })
+            .collect()
+    }
+
+    fn roles(&self) -> HashSet<RoleId> {
+        let mut roles = HashSet::new();
+        for section in self.permissions.values() {
+            roles.extend(&section.roles);
+            roles.extend(&section.deny_roles);
+            for scope in &section.scopes {
+                roles.extend(&scope.roles);
+                roles.extend(&scope.deny_roles);
+            }
+        }
+        roles
+    }
+
+    fn channels(&self) -> HashSet<ChannelId> {
+        let mut channels = HashSet::new();
+        for section in self.permissions.values() {
+            channels.extend(section.scopes.iter().map(|scope| scope.channel));
+        }
+        channels.extend(&self.previews.channels);
+        channels.extend(&self.previews.webhook_channels);
+        channels.extend(&self.previews.archive);
+        channels.extend(&self.moderation.log_channel);
+        channels
+    }
+
+    // swaps every role and channel id the maps know about
+    fn remap(&mut self, roles: &HashMap<RoleId, RoleId>, channels: &HashMap<ChannelId, ChannelId>) {
+        let role = |id: &mut RoleId| *id = *roles.get(id).unwrap_or(id);
+        let channel = |id: &mut ChannelId| *id = *channels.get(id).unwrap_or(id);
+
+        for section in self.permissions.values_mut() {
+            section.roles.iter_mut().for_each(role);
+            section.deny_roles.iter_mut().for_each(role);
+            for scope in &mut section.scopes {
+                channel(&mut scope.channel);
+                scope.roles.iter_mut().for_each(role);
+                scope.deny_roles.iter_mut().for_each(role);
+            }
+        }
+        self.previews.channels.iter_mut().for_each(channel);
+        self.previews.webhook_channels.iter_mut().for_each(channel);
+        self.previews.archive.iter_mut().for_each(channel);
+        self.moderation.log_channel.iter_mut().for_each(channel);
+        self.names
+            .roles
+            .iter_mut()
+            .for_each(|named| role(&mut named.id));
+        self.names
+            .channels
+            .iter_mut()
+            .for_each(|named| channel(&mut named.id));
+    }
+}
+
+struct PendingImport {
+    user: UserId,
+    config: ConfigFile,
+    created: Instant,
+}
+
+pub struct ConfigModule {
+    permissions: Arc<PermissionsModule>,
+    previews: Arc<PreviewsModule>,
+    utils: Arc<UtilsModule>,
+    pending: RwLock<HashMap<GuildId, PendingImport>>,
+}
+
+impl ConfigModule {
+    pub fn new(
+        permissions: Arc<PermissionsModule>,
+        previews: Arc<PreviewsModule>,
+        utils: Arc<UtilsModule>,
+    ) -> Self {
+        Self {
+            permissions,
+            previews,
+            utils,
+            pending: Default::default(),
+        }
+    }
+
+    async fn export(&self, guild_id: GuildId) -> ConfigFile {
+        ConfigFile {
+            version: CONFIG_VERSION,
+            permissions: self.permissions.export_config(&guild_id).await,
+            keep_user_grants: self.permissions.keep_user_grants(&guild_id).await,
+            previews: self.previews.export_config(&guild_id).await,
+            moderation: self.utils.export_config(&guild_id).await,
+            names: Default::default(),
+        }
+    }
+
+    // every role and channel of the server by name, active threads included
+    fn guild_names(
+        ctx: &BotContext,
+        guild_id: GuildId,
+    ) -> Result<(HashMap<RoleId, String>, HashMap<ChannelId, String>)> {
+        Ok(ctx
+            .cache
+            .guild_field(guild_id, |g| {
+                (
+                    g.roles
+                        .iter()
+                        .map(|(id, role)| (*id, role.name.clone()))
+                        .collect(),
+                    g.channels
+                        .iter()
+                        .filter_map(|(id, channel)| Some((*id, s!(channel_name(channel)?))))
+                        .chain(
+                            g.threads
+                                .iter()
+                                .map(|thread| (thread.id, thread.name.clone())),
+                        )
+                        .collect(),
+                )
+            })
+            .ok_or(BotError::CacheMissing)?)
+    }
+
+    fn name_section(
+        ctx: &BotContext,
+        guild_id: GuildId,
+        config: &ConfigFile,
+    ) -> Result<NameSection> {
+        let (roles, channels) = Self::guild_names(ctx, guild_id)?;
+        let mut names = NameSection {
+            roles: config
+                .roles()
+                .into_iter()
+                .filter_map(|id| {
+                    Some(NamedId {
+                        id,
+                        name: roles.get(&id)?.clone(),
+                    })
+                })
+                .collect(),
+            channels: config
+                .channels()
+                .into_iter()
+                .filter_map(|id| {
+                    Some(NamedId {
+                        id,
+                        name: channels.get(&id)?.clone(),
+                    })
+                })
+                .collect(),
+        };
+        names.roles.sort_by_key(|named| named.id);
+        names.channels.sort_by_key(|named| named.id);
+        Ok(names)
+    }
+
+    // channels the server doesn't have, asking discord about the ones missing from the cache
+    // since archived threads never are in it
+    async fn missing_channels(
+        ctx: &BotContext,
+        guild_id: GuildId,
+        config: &ConfigFile,
+        cached: &HashMap<ChannelId, String>,
+    ) -> Vec<ChannelId> {
+        let mut missing = Vec::new();
+        for channel in config.channels() {
+            if cached.contains_key(&channel) {
+                continue;
+            }
+            match channel.to_channel(ctx).await {
+                Ok(Channel::Guild(found)) if found.guild_id == guild_id => {}
+                _ => missing.push(channel),
+            }
+        }
+        missing.sort();
+        missing
+    }
+
+    fn missing_roles(config: &ConfigFile, cached: &HashMap<RoleId, String>) -> Vec<RoleId> {
+        let mut missing = config
+            .roles()
+            .into_iter()
+            .filter(|role| !cached.contains_key(role))
+            .collect::<Vec<RoleId>>();
+        missing.sort();
+        missing
+    }
+
+    // points roles and channels from another server at the ones with the same name here,
+    // returning mentions of everything that was matched
+    async fn match_names(
+        ctx: &BotContext,
+        guild_id: GuildId,
+        config: &mut ConfigFile,
+    ) -> Result<Vec<String>> {
+        let (roles, channels) = Self::guild_names(ctx, guild_id)?;
+        let roles = match_names(
+            Self::missing_roles(config, &roles),
+            &config.names.roles,
+            &roles,
+        );
+        let channels = match_names(
+            Self::missing_channels(ctx, guild_id, config, &channels).await,
+            &config.names.channels,
+            &channels,
+        );
+        config.remap(&roles, &channels);
+
+        let mut matched = roles
+            .values()
+            .map(|role| role.mention().to_string())
+            .chain(
+                channels
+                    .values()
+                    .map(|channel| channel.mention().to_string()),
+            )
+            .collect::<Vec<String>>();
+        matched.sort();
+        Ok(matched)
+    }
+
+    // checks everything an imported file refers to, returning what's wrong with it
+    async fn validate(
+        ctx: &BotContext,
+        guild_id: GuildId,
+        config: &ConfigFile,
+    ) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+
+        if config.version != CONFIG_VERSION {
+            problems.push(format!(
+                "Unsupported configuration version {} (expected {})",
+                config.version, CONFIG_VERSION
+            ));
+            return Ok(problems);
+        }
+
+        for ty in config.permissions.keys() {
+            if PermissionType::from_string(ty).is_err() {
+                problems.push(format!("Unknown permission `{}`", ty));
+            }
+        }
+
+        let (roles, channels) = Self::guild_names(ctx, guild_id)?;
+
+        let missing_roles = Self::missing_roles(config, &roles);
+        if !missing_roles.is_empty() {
+            problems.push(format!(
+                "Unknown roles {}",
+                describe_missing(&missing_roles, &config.names.roles)
+            ));
+        }
+
+        let missing_channels = Self::missing_channels(ctx, guild_id, config, &channels).await;
+        if !missing_channels.is_empty() {
+            problems.push(format!(
+                "Unknown channels {}",
+                describe_missing(&missing_channels, &config.names.channels)
+            ));
+        }
+
+        problems.extend(UtilsModule::validate_config(&config.moderation));
+
+        Ok(problems)
+    }
+
+    pub async fn config_export(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+
+        let mut config = self.export(guild_id).await;
+        config.names = Self::name_section(ctx, guild_id, &config)?;
+        let data = serde_json::to_vec_pretty(&config)?;
+
+        interaction
+            .create_followup_message(&ctx.http, |m| {
+                m.content("Configuration for this server, import it with `/config import`")
+                    .add_file(AttachmentType::Bytes {
+                        data: Cow::from(data),
+                        filename: format!("makita-{}.json", guild_id),
+                    })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn config_import(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let attachment = args.get_attachment("file")?;
+
+        if attachment.size > MAX_CONFIG_SIZE {
+            return Err(Error::new(BotError::Generic(s!(
+                "File is too large to be a configuration"
+            ))));
+        }
+
+        let bytes = attachment.download().await?;
+        let mut config = serde_json::from_slice::<ConfigFile>(&bytes).map_err(|e| {
+            Error::new(BotError::Generic(format!(
+                "Invalid configuration file: {}",
+                e
+            )))
+        })?;
+
+        let matched = Self::match_names(ctx, guild_id, &mut config).await?;
+        let problems = Self::validate(ctx, guild_id, &config).await?;
+        if !problems.is_empty() {
+            return Err(Error::new(BotError::Generic(format!(
+                "Can't import this configuration:\n{}",
+                problems.join("\n")
+            ))));
+        }
+
+        // missing permissions in the file mean defaults, so compare against the filled in version
+        PermissionsModule::fill_config(&mut config.permissions);
+
+        let diff = self.export(guild_id).await.diff(&config);
+        if diff.is_empty() {
+            return FollowupBuilder::new()
+                .description("Configuration is identical, nothing to import")
+                .build_command_followup(&ctx, interaction)
+                .await;
+        }
+
+        let expiries = PermissionsModule::pending_expiries(&ctx.pool, &guild_id).await?;
+
+        self.pending.write().await.insert(
+            guild_id,
+            PendingImport {
+                user: interaction.user.id,
+                config,
+                created: Instant::now(),
+            },
+        );
+
+        let button_id = |action: &str| {
+            build_custom_id(
+                &CustomIdType::ConfigImport,
+                &Some(HashMap::from([(s!("action"), s!(action))])),
+            )
+        };
+
+        interaction
+            .create_followup_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.title("Import configuration?")
+                        .description(format!(
+                            "{} sections will change. Everything not listed stays the same.{}{}",
+                            diff.len(),
+                            if matched.is_empty() {
+                                s!("")
+                            } else {
+                                format!("\nMatched by name: {}", matched.join(" "))
+                            },
+                            if expiries == 0 {
+                                s!("")
+                            } else {
+                                format!(
+                                    "\n{} pending permission expiries will be cancelled, imported grants are permanent",
+                                    expiries
+                                )
+                            }
+                        ))
+                        .color(Color::GOLD);
+                    for (label, before, after) in diff.iter().take(MAX_DIFF_FIELDS) {
+                        let mut value = format!("**Before**\n{}\n**After**\n{}", before, after);
+                        if value.chars().count() > MAX_FIELD_LENGTH {
+                            value = value.chars().take(MAX_FIELD_LENGTH - 1).collect();
+                            value.push('…');
+                        }
+                        e.field(label, value, false);
+                    }
+                    e
+                })
+                .components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_button(|b| {
+                            b.custom_id(button_id("apply"))
+                                .label("Import")
+                                .style(ButtonStyle::Danger)
+                        })
+                        .create_button(|b| {
+                            b.custom_id(button_id("cancel"))
+                                .label("Cancel")
+                                .style(ButtonStyle::Secondary)
+                        })
+                    })
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn import_component(
+        &self,
+        ctx: &BotContext,
+        interaction: &MessageComponentInteraction,
+        args: HashMap<String, String>,
+    ) -> Result<()> {
+        defer_component(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+
+        let pending = {
+            let mut handle = self.pending.write().await;
+            match handle.get(&guild_id) {
+                Some(pending) if pending.user != interaction.user.id => {
+                    return FollowupBuilder::new()
+                        .description("Only the person who started this import can confirm it")
+                        .ephemeral()
+                        .build_component_followup(&ctx, interaction)
+                        .await;
+                }
+                Some(_) => handle.remove(&guild_id),
+                None => None,
+            }
+        };
+
+        let outcome = match (args.get("action").map(String::as_str), pending) {
+            (Some("apply"), Some(pending)) if pending.created.elapsed() < IMPORT_TIMEOUT => {
+                // the server may have changed since the preview was shown
+                let problems = Self::validate(ctx, guild_id, &pending.config).await?;
+                if problems.is_empty() {
+                    self.apply(ctx, guild_id, &interaction.user.id, &pending.config)
+                        .await?;
+                    s!("Configuration imported")
+                } else {
+                    format!(
+                        "Can't import this configuration anymore:\n{}",
+                        problems.join("\n")
+                    )
+                }
+            }
+            (Some("apply"), _) => s!("This import expired, run `/config import` again"),
+            _ => s!("Import cancelled"),
+        };
+
+        interaction
+            .edit_original_interaction_response(&ctx.http, |m| {
+                m.embed(|e| e.description(outcome)).components(|c| c)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        ctx: &BotContext,
+        guild_id: GuildId,
+        actor: &UserId,
+        config: &ConfigFile,
+    ) -> Result<()> {
+        let diff = self.export(guild_id).await.diff(config);
+
+        // everything goes in at once, a failure halfway would leave a mix of both configurations
+        let mut tx = ctx.pool.begin().await?;
+        self.permissions
+            .import_config(
+                &mut tx,
+                &guild_id,
+                &config.permissions,
+                config.keep_user_grants,
+            )
+            .await?;
+        self.previews
+            .import_config(&mut tx, &guild_id, &config.previews)
+            .await?;
+        self.utils
+            .import_config(&mut tx, &guild_id, &config.moderation)
+            .await?;
+
+        for (label, before, after) in diff {
+            AuditEntry {
+                guild_id,
+                actor: *actor,
+                action: AuditAction::ConfigImport,
+                target: label,
+                before: Some(before),
+                after: Some(after),
+            }
+            .record_in(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        self.permissions
+            .cache_config(&guild_id, &config.permissions, config.keep_user_grants)
+            .await;
+        self.previews
+            .cache_config(&guild_id, &config.previews)
+            .await;
+        self.utils.cache_config(&guild_id, &config.moderation).await;
+
+        Ok(())
+    }
+}
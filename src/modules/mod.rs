@@ -3,11 +3,17 @@
 // You should have received a copy of the license along with this program
 // If not, see <https://www.gnu.org/licenses/#AGPL>
 
+pub mod audit;
+pub mod cases;
+pub mod config;
 pub mod permissions;
 pub mod previews;
 pub mod updates;
 pub mod utils;
 
+pub use audit::AuditModule;
+pub use cases::CasesModule;
+pub use config::ConfigModule;
 pub use permissions::*;
 pub use previews::*;
 pub use updates::UpdatesModule;
@@ -3,23 +3,34 @@
 // You should have received a copy of the license along with this program
 // If not, see <https://www.gnu.org/licenses/#AGPL>
 
-use crate::custom_ids::{build_custom_id, CustomIdType};
+use crate::custom_ids::CustomIdType;
 use crate::decode::SlashMap;
 use crate::impl_cache_functions;
+use crate::modules::audit::{AuditAction, AuditEntry};
+use crate::modules::config::{PermissionSection, ScopeSection};
 use crate::prelude::*;
 use crate::tasks::TaskMessage;
-use crate::utils::{defer_command, defer_component, BotContext, FollowupBuilder, SqlId};
+use crate::utils::{
+    create_page_buttons, defer_command, defer_component, parse_duration, BotContext,
+    FollowupBuilder, SqlId,
+};
 use anyhow::{Error, Result};
+use chrono::Utc;
 use futures::FutureExt;
-use serenity::builder::CreateSelectMenuOptions;
-use serenity::model::guild::Role;
-use serenity::model::id::{GuildId, RoleId, UserId};
+use serenity::builder::CreateEmbed;
+use serenity::model::guild::{Guild, Role};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::interaction::autocomplete::AutocompleteInteraction;
 use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::user::User;
 use serenity::model::Permissions as DiscordPermissions;
 use serenity::prelude::Mentionable;
-use sqlx::{PgPool, Row};
-use std::collections::{HashMap, HashSet};
+use serenity::utils::Color;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Row};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
 use tokio::sync::{broadcast, RwLock};
 
 macro_rules! impl_permission_type {
@@ -52,9 +63,14 @@ macro_rules! impl_permission_type {
                 }
             }
 
-            pub fn build_select_menu_options(builder: &mut CreateSelectMenuOptions) -> &mut CreateSelectMenuOptions {
-                $(builder.create_option(|o| o.label($display).value($value).description($desc)));+;
-                builder
+            pub fn all() -> &'static [PermissionType] {
+                &[$(PermissionType::$enum),+]
+            }
+
+            pub fn as_description(&self) -> &'static str {
+                match self {
+                    $(PermissionType::$enum => $desc),+
+                }
             }
         }
     }
@@ -80,13 +96,275 @@ impl_permission_type!(
     Timeout,
     "Timeout",
     "Timeout",
-    "Timeout users"
+    "Timeout users",
+    ViewAuditLog,
+    "ViewAuditLog",
+    "View Audit Log",
+    "View the configuration audit log",
+    ManageCases,
+    "ManageCases",
+    "Manage Cases",
+    "View and edit moderation cases",
+    Kick,
+    "Kick",
+    "Kick",
+    "Kick users",
+    Ban,
+    "Ban",
+    "Ban",
+    "Ban, softban and unban users"
 );
 
+// permission types shown per page of the overview
+const LIST_PAGE_SIZE: usize = 3;
+// discord caps embed field values at 1024 characters
+const MAX_FIELD_LENGTH: usize = 1024;
+
+// names accepted by `/permissions set`, discord's own flag names in snake case
+const DISCORD_PERMISSION_NAMES: [(&str, DiscordPermissions); 41] = [
+    (
+        "create_instant_invite",
+        DiscordPermissions::CREATE_INSTANT_INVITE,
+    ),
+    ("kick_members", DiscordPermissions::KICK_MEMBERS),
+    ("ban_members", DiscordPermissions::BAN_MEMBERS),
+    ("administrator", DiscordPermissions::ADMINISTRATOR),
+    ("manage_channels", DiscordPermissions::MANAGE_CHANNELS),
+    ("manage_guild", DiscordPermissions::MANAGE_GUILD),
+    ("add_reactions", DiscordPermissions::ADD_REACTIONS),
+    ("view_audit_log", DiscordPermissions::VIEW_AUDIT_LOG),
+    ("priority_speaker", DiscordPermissions::PRIORITY_SPEAKER),
+    ("stream", DiscordPermissions::STREAM),
+    ("view_channel", DiscordPermissions::VIEW_CHANNEL),
+    ("send_messages", DiscordPermissions::SEND_MESSAGES),
+    ("send_tts_messages", DiscordPermissions::SEND_TTS_MESSAGES),
+    ("manage_messages", DiscordPermissions::MANAGE_MESSAGES),
+    ("embed_links", DiscordPermissions::EMBED_LINKS),
+    ("attach_files", DiscordPermissions::ATTACH_FILES),
+    (
+        "read_message_history",
+        DiscordPermissions::READ_MESSAGE_HISTORY,
+    ),
+    ("mention_everyone", DiscordPermissions::MENTION_EVERYONE),
+    (
+        "use_external_emojis",
+        DiscordPermissions::USE_EXTERNAL_EMOJIS,
+    ),
+    (
+        "view_guild_insights",
+        DiscordPermissions::VIEW_GUILD_INSIGHTS,
+    ),
+    ("connect", DiscordPermissions::CONNECT),
+    ("speak", DiscordPermissions::SPEAK),
+    ("mute_members", DiscordPermissions::MUTE_MEMBERS),
+    ("deafen_members", DiscordPermissions::DEAFEN_MEMBERS),
+    ("move_members", DiscordPermissions::MOVE_MEMBERS),
+    ("use_vad", DiscordPermissions::USE_VAD),
+    ("change_nickname", DiscordPermissions::CHANGE_NICKNAME),
+    ("manage_nicknames", DiscordPermissions::MANAGE_NICKNAMES),
+    ("manage_roles", DiscordPermissions::MANAGE_ROLES),
+    ("manage_webhooks", DiscordPermissions::MANAGE_WEBHOOKS),
+    (
+        "manage_emojis_and_stickers",
+        DiscordPermissions::MANAGE_EMOJIS_AND_STICKERS,
+    ),
+    ("use_slash_commands", DiscordPermissions::USE_SLASH_COMMANDS),
+    ("request_to_speak", DiscordPermissions::REQUEST_TO_SPEAK),
+    ("manage_events", DiscordPermissions::MANAGE_EVENTS),
+    ("manage_threads", DiscordPermissions::MANAGE_THREADS),
+    (
+        "create_public_threads",
+        DiscordPermissions::CREATE_PUBLIC_THREADS,
+    ),
+    (
+        "create_private_threads",
+        DiscordPermissions::CREATE_PRIVATE_THREADS,
+    ),
+    (
+        "use_external_stickers",
+        DiscordPermissions::USE_EXTERNAL_STICKERS,
+    ),
+    (
+        "send_messages_in_threads",
+        DiscordPermissions::SEND_MESSAGES_IN_THREADS,
+    ),
+    (
+        "use_embedded_activities",
+        DiscordPermissions::USE_EMBEDDED_ACTIVITIES,
+    ),
+    ("moderate_members", DiscordPermissions::MODERATE_MEMBERS),
+];
+// discord allows at most 25 autocomplete choices, each value up to 100 characters
+const MAX_CHOICES: usize = 25;
+const MAX_CHOICE_LENGTH: usize = 100;
+
+/// Parses a comma separated list of permission names, "none", or raw bits
+fn parse_discord_permissions(from: &str) -> std::result::Result<DiscordPermissions, String> {
+    let from = from.trim();
+    if from.eq_ignore_ascii_case("none") {
+        return Ok(DiscordPermissions::empty());
+    }
+    if let Ok(bits) = u64::from_str(from) {
+        return DiscordPermissions::from_bits(bits).ok_or_else(|| s!("Invalid permission bits"));
+    }
+
+    let mut permissions = DiscordPermissions::empty();
+    for name in from
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let normalized = name.to_ascii_lowercase().replace([' ', '-'], "_");
+        match DISCORD_PERMISSION_NAMES
+            .iter()
+            .find(|(known, _)| *known == normalized)
+        {
+            Some((_, permission)) => permissions.insert(*permission),
+            None => return Err(format!("Unknown Discord permission `{}`", name)),
+        }
+    }
+    Ok(permissions)
+}
+
+/// Which list of an entry a command works on
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Grant {
+    Allow,
+    Deny,
+}
+
+fn mentions<T: Mentionable>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|x| x.mention().to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// The rule that made a permission entry match
+#[derive(Copy, Clone)]
+pub enum RuleMatch {
+    Bits(DiscordPermissions),
+    User(UserId),
+    Role(RoleId),
+    ScopedUser(ChannelId, UserId),
+    ScopedRole(ChannelId, RoleId),
+}
+
+impl RuleMatch {
+    fn describe(&self) -> String {
+        match self {
+            RuleMatch::Bits(bits) => format!("Discord permissions `{}`", bits),
+            RuleMatch::User(user) => format!("User {}", user.mention()),
+            RuleMatch::Role(role) => format!("Role {}", role.mention()),
+            RuleMatch::ScopedUser(scope, user) => {
+                format!("User {} in {}", user.mention(), scope.mention())
+            }
+            RuleMatch::ScopedRole(scope, role) => {
+                format!("Role {} in {}", role.mention(), scope.mention())
+            }
+        }
+    }
+}
+
+/// What each kind of rule in a permission entry matched, checked separately
+#[derive(Default)]
+struct EntryMatch {
+    bits: Option<RuleMatch>,
+    role: Option<RuleMatch>,
+    user: Option<RuleMatch>,
+}
+
+impl EntryMatch {
+    fn is_match(&self) -> bool {
+        self.bits.is_some() || self.role.is_some() || self.user.is_some()
+    }
+
+    // a trace line per kind of rule, discord bits only ever allow
+    fn steps(&self, label: &str, grant: Grant) -> Vec<(String, String)> {
+        let describe = |found: &Option<RuleMatch>| match found {
+            Some(found) => found.describe(),
+            None => s!("No match"),
+        };
+        let mut steps = Vec::with_capacity(3);
+        if grant == Grant::Allow {
+            steps.push((
+                format!("{} Discord permissions", label),
+                describe(&self.bits),
+            ));
+        }
+        steps.push((format!("{} role", label), describe(&self.role)));
+        steps.push((format!("{} user", label), describe(&self.user)));
+        steps
+    }
+}
+
+/// Outcome of a permission check along with the steps that led to it
+pub struct PermissionTrace {
+    pub steps: Vec<(String, String)>,
+    pub allowed: bool,
+}
+
+/// Extra allows and denies that only apply inside a channel or category
+#[derive(Default)]
+pub struct ScopedPermissionData {
+    roles: Vec<RoleId>,
+    users: Vec<UserId>,
+    deny_roles: Vec<RoleId>,
+    deny_users: Vec<UserId>,
+}
+
+impl ScopedPermissionData {
+    fn is_empty(&self) -> bool {
+        self.roles.is_empty()
+            && self.users.is_empty()
+            && self.deny_roles.is_empty()
+            && self.deny_users.is_empty()
+    }
+
+    fn lists(&self, grant: Grant) -> (&Vec<RoleId>, &Vec<UserId>) {
+        match grant {
+            Grant::Allow => (&self.roles, &self.users),
+            Grant::Deny => (&self.deny_roles, &self.deny_users),
+        }
+    }
+
+    fn lists_mut(&mut self, grant: Grant) -> (&mut Vec<RoleId>, &mut Vec<UserId>) {
+        match grant {
+            Grant::Allow => (&mut self.roles, &mut self.users),
+            Grant::Deny => (&mut self.deny_roles, &mut self.deny_users),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.roles.is_empty() {
+            parts.push(format!("Roles {}", mentions(&self.roles)));
+        }
+        if !self.users.is_empty() {
+            parts.push(format!("Users {}", mentions(&self.users)));
+        }
+        if !self.deny_roles.is_empty() {
+            parts.push(format!("Denied roles {}", mentions(&self.deny_roles)));
+        }
+        if !self.deny_users.is_empty() {
+            parts.push(format!("Denied users {}", mentions(&self.deny_users)));
+        }
+        if parts.is_empty() {
+            s!("Nothing")
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
 pub struct GuildPermissionData {
     discord: DiscordPermissions,
     roles: Vec<RoleId>,
     users: Vec<UserId>,
+    deny_roles: Vec<RoleId>,
+    deny_users: Vec<UserId>,
+    scopes: HashMap<ChannelId, ScopedPermissionData>,
 }
 
 impl GuildPermissionData {
@@ -95,6 +373,23 @@ impl GuildPermissionData {
             discord: *permissions,
             roles: Vec::new(),
             users: Vec::new(),
+            deny_roles: Vec::new(),
+            deny_users: Vec::new(),
+            scopes: HashMap::new(),
+        }
+    }
+
+    fn lists(&self, grant: Grant) -> (&Vec<RoleId>, &Vec<UserId>) {
+        match grant {
+            Grant::Allow => (&self.roles, &self.users),
+            Grant::Deny => (&self.deny_roles, &self.deny_users),
+        }
+    }
+
+    fn lists_mut(&mut self, grant: Grant) -> (&mut Vec<RoleId>, &mut Vec<UserId>) {
+        match grant {
+            Grant::Allow => (&mut self.roles, &mut self.users),
+            Grant::Deny => (&mut self.deny_roles, &mut self.deny_users),
         }
     }
 
@@ -105,7 +400,125 @@ impl GuildPermissionData {
             PermissionType::ManagePreviews => Self::new(&DiscordPermissions::MANAGE_GUILD),
             PermissionType::CreateArchive => Self::new(&DiscordPermissions::MANAGE_MESSAGES),
             PermissionType::Timeout => Self::new(&DiscordPermissions::MODERATE_MEMBERS),
+            PermissionType::ViewAuditLog => Self::new(&DiscordPermissions::MANAGE_GUILD),
+            PermissionType::ManageCases => Self::new(&DiscordPermissions::MODERATE_MEMBERS),
+            PermissionType::Kick => Self::new(&DiscordPermissions::KICK_MEMBERS),
+            PermissionType::Ban => Self::new(&DiscordPermissions::BAN_MEMBERS),
+        }
+    }
+
+    fn to_section(&self) -> PermissionSection {
+        let mut scopes = self
+            .scopes
+            .iter()
+            .map(|(channel, scoped)| ScopeSection {
+                channel: *channel,
+                roles: scoped.roles.clone(),
+                users: scoped.users.clone(),
+                deny_roles: scoped.deny_roles.clone(),
+                deny_users: scoped.deny_users.clone(),
+            })
+            .collect::<Vec<ScopeSection>>();
+        scopes.sort_by_key(|scope| scope.channel);
+        PermissionSection {
+            discord: self.discord.bits(),
+            roles: self.roles.clone(),
+            users: self.users.clone(),
+            deny_roles: self.deny_roles.clone(),
+            deny_users: self.deny_users.clone(),
+            scopes,
+        }
+    }
+
+    fn from_section(section: &PermissionSection) -> Self {
+        Self {
+            discord: DiscordPermissions::from_bits_truncate(section.discord),
+            roles: section.roles.clone(),
+            users: section.users.clone(),
+            deny_roles: section.deny_roles.clone(),
+            deny_users: section.deny_users.clone(),
+            scopes: section
+                .scopes
+                .iter()
+                .map(|scope| {
+                    (
+                        scope.channel,
+                        ScopedPermissionData {
+                            roles: scope.roles.clone(),
+                            users: scope.users.clone(),
+                            deny_roles: scope.deny_roles.clone(),
+                            deny_users: scope.deny_users.clone(),
+                        },
+                    )
+                })
+                .filter(|(_, scoped)| !scoped.is_empty())
+                .collect(),
+        }
+    }
+
+    // multi-line summary, used for the permission overview
+    fn summary(&self) -> String {
+        let names = self.discord.get_permission_names();
+        let mut lines = vec![format!(
+            "**Discord** {}",
+            if names.is_empty() {
+                s!("None")
+            } else {
+                names.join(", ")
+            }
+        )];
+        if !self.roles.is_empty() {
+            lines.push(format!("**Roles** {}", mentions(&self.roles)));
+        }
+        if !self.users.is_empty() {
+            lines.push(format!("**Users** {}", mentions(&self.users)));
         }
+        if !self.deny_roles.is_empty() || !self.deny_users.is_empty() {
+            lines.push(format!(
+                "**Denied** {}",
+                [mentions(&self.deny_roles), mentions(&self.deny_users)]
+                    .iter()
+                    .filter(|x| !x.is_empty())
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ));
+        }
+        let mut scopes = self.scopes.iter().collect::<Vec<_>>();
+        scopes.sort_by_key(|(scope, _)| **scope);
+        for (scope, scoped) in scopes {
+            lines.push(format!("**In** {}: {}", scope.mention(), scoped.describe()));
+        }
+
+        let summary = lines.join("\n");
+        if summary.chars().count() > MAX_FIELD_LENGTH {
+            let mut truncated = summary
+                .chars()
+                .take(MAX_FIELD_LENGTH - 1)
+                .collect::<String>();
+            truncated.push('…');
+            truncated
+        } else {
+            summary
+        }
+    }
+
+    // human readable summary, used for the audit log
+    fn describe(&self) -> String {
+        let mut parts = vec![format!("Bits `{}`", self.discord.bits())];
+        if !self.roles.is_empty() {
+            parts.push(format!("Roles {}", mentions(&self.roles)));
+        }
+        if !self.users.is_empty() {
+            parts.push(format!("Users {}", mentions(&self.users)));
+        }
+        if !self.deny_roles.is_empty() {
+            parts.push(format!("Denied roles {}", mentions(&self.deny_roles)));
+        }
+        if !self.deny_users.is_empty() {
+            parts.push(format!("Denied users {}", mentions(&self.deny_users)));
+        }
+        parts.join(", ")
     }
 }
 
@@ -113,6 +526,8 @@ impl GuildPermissionData {
 pub struct GuildPermissionEntry {
     data: HashMap<PermissionType, GuildPermissionData>,
     guild_id: GuildId,
+    // keeps user grants around when the member leaves, so they apply again on rejoin
+    keep_user_grants: bool,
 }
 
 impl GuildPermissionEntry {
@@ -120,6 +535,7 @@ impl GuildPermissionEntry {
         let mut entry = Self {
             data: Default::default(),
             guild_id: *guild_id,
+            keep_user_grants: false,
         };
         entry.data.insert(
             PermissionType::Administrator,
@@ -141,6 +557,22 @@ impl GuildPermissionEntry {
             PermissionType::Timeout,
             GuildPermissionData::default(&PermissionType::Timeout),
         );
+        entry.data.insert(
+            PermissionType::ViewAuditLog,
+            GuildPermissionData::default(&PermissionType::ViewAuditLog),
+        );
+        entry.data.insert(
+            PermissionType::ManageCases,
+            GuildPermissionData::default(&PermissionType::ManageCases),
+        );
+        entry.data.insert(
+            PermissionType::Kick,
+            GuildPermissionData::default(&PermissionType::Kick),
+        );
+        entry.data.insert(
+            PermissionType::Ban,
+            GuildPermissionData::default(&PermissionType::Ban),
+        );
         entry
     }
 
@@ -153,25 +585,230 @@ impl GuildPermissionEntry {
         self.data.get_mut(ty).unwrap()
     }
 
-    pub async fn set<F>(&mut self, ty: &PermissionType, pool: &PgPool, mut func: F) -> Result<()>
+    pub async fn set<F>(
+        &mut self,
+        ty: &PermissionType,
+        pool: &PgPool,
+        actor: &UserId,
+        mut func: F,
+    ) -> Result<()>
     where
         F: FnMut(&mut GuildPermissionData),
     {
         let data = self.data.get_mut(ty).unwrap();
+        let before = data.describe();
         func(data);
-        sqlx::query("insert into Permissions (type, guild_id, overwrites, roles, users) values ($1, $2, $3, $4, $5)\
-                         on conflict on constraint permissions_idx do update set overwrites = $3, roles = $4, users = $5")
+        let after = data.describe();
+        self.save(ty, pool).await?;
+
+        if before != after {
+            AuditEntry {
+                guild_id: self.guild_id,
+                actor: *actor,
+                action: AuditAction::Permission,
+                target: ty.as_display().to_string(),
+                before: Some(before),
+                after: Some(after),
+            }
+            .record(pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn save(&self, ty: &PermissionType, pool: &PgPool) -> Result<()> {
+        self.save_in(ty, &mut *pool.acquire().await?).await
+    }
+
+    async fn save_in(&self, ty: &PermissionType, conn: &mut PgConnection) -> Result<()> {
+        let data = self.get(ty);
+        sqlx::query("insert into Permissions (type, guild_id, overwrites, roles, users, deny_roles, deny_users) values ($1, $2, $3, $4, $5, $6, $7)\
+                         on conflict on constraint permissions_idx do update set overwrites = $3, roles = $4, users = $5, deny_roles = $6, deny_users = $7")
             .bind(ty)
-            .bind(&SqlId(self.guild_id))
-            .bind(&SqlId(data.discord.bits()))
+            .bind(SqlId(self.guild_id))
+            .bind(SqlId(data.discord.bits()))
             .bind(data.roles.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
             .bind(data.users.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+            .bind(data.deny_roles.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+            .bind(data.deny_users.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    // replaces every stored override of a permission with the cached ones
+    async fn save_scopes(&self, ty: &PermissionType, pool: &PgPool) -> Result<()> {
+        // the delete and inserts land together, or a failure would lose every override
+        let mut tx = pool.begin().await?;
+        self.save_scopes_in(ty, &mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn save_scopes_in(&self, ty: &PermissionType, conn: &mut PgConnection) -> Result<()> {
+        sqlx::query("delete from PermissionOverrides where guild_id = $1 and type = $2")
+            .bind(SqlId(self.guild_id))
+            .bind(ty)
+            .execute(&mut *conn)
+            .await?;
+        for (scope, data) in &self.get(ty).scopes {
+            sqlx::query("insert into PermissionOverrides (guild_id, type, scope, roles, users, deny_roles, deny_users) values ($1, $2, $3, $4, $5, $6, $7)")
+                .bind(SqlId(self.guild_id))
+                .bind(ty)
+                .bind(SqlId(*scope))
+                .bind(data.roles.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .bind(data.users.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .bind(data.deny_roles.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .bind(data.deny_users.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Drops every role and user the predicates call stale, returning what was removed per type
+    async fn prune<R, U>(
+        &mut self,
+        pool: &PgPool,
+        mut stale_role: R,
+        mut stale_user: U,
+    ) -> Result<Vec<(PermissionType, Vec<String>)>>
+    where
+        R: FnMut(&RoleId) -> bool,
+        U: FnMut(&UserId) -> bool,
+    {
+        let mut pruned = Vec::new();
+        for (ty, data) in self.data.iter_mut() {
+            let mut removed = Vec::new();
+            let mut prune_lists = |roles: &mut Vec<RoleId>, users: &mut Vec<UserId>| {
+                roles.retain(|role| {
+                    let stale = stale_role(role);
+                    if stale {
+                        removed.push(role.mention().to_string());
+                    }
+                    !stale
+                });
+                users.retain(|user| {
+                    let stale = stale_user(user);
+                    if stale {
+                        removed.push(user.mention().to_string());
+                    }
+                    !stale
+                });
+            };
+            prune_lists(&mut data.roles, &mut data.users);
+            prune_lists(&mut data.deny_roles, &mut data.deny_users);
+            for scoped in data.scopes.values_mut() {
+                prune_lists(&mut scoped.roles, &mut scoped.users);
+                prune_lists(&mut scoped.deny_roles, &mut scoped.deny_users);
+            }
+            data.scopes.retain(|_, scoped| !scoped.is_empty());
+            if !removed.is_empty() {
+                removed.sort_unstable();
+                removed.dedup();
+                pruned.push((*ty, removed));
+            }
+        }
+
+        for (ty, _) in &pruned {
+            self.save(ty, pool).await?;
+            self.save_scopes(ty, pool).await?;
+        }
+        Ok(pruned)
+    }
+
+    pub async fn set_scoped<F>(
+        &mut self,
+        ty: &PermissionType,
+        scope: ChannelId,
+        pool: &PgPool,
+        actor: &UserId,
+        mut func: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&mut ScopedPermissionData),
+    {
+        let scopes = &mut self.data.get_mut(ty).unwrap().scopes;
+        let data = scopes.entry(scope).or_default();
+        let before = data.describe();
+        func(data);
+        let after = data.describe();
+
+        if data.is_empty() {
+            scopes.remove(&scope);
+            sqlx::query(
+                "delete from PermissionOverrides where guild_id = $1 and type = $2 and scope = $3",
+            )
+            .bind(SqlId(self.guild_id))
+            .bind(ty)
+            .bind(SqlId(scope))
             .execute(pool)
             .await?;
+        } else {
+            sqlx::query("insert into PermissionOverrides (guild_id, type, scope, roles, users, deny_roles, deny_users) values ($1, $2, $3, $4, $5, $6, $7)\
+                             on conflict on constraint permission_overrides_idx do update set roles = $4, users = $5, deny_roles = $6, deny_users = $7")
+                .bind(SqlId(self.guild_id))
+                .bind(ty)
+                .bind(SqlId(scope))
+                .bind(data.roles.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .bind(data.users.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .bind(data.deny_roles.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .bind(data.deny_users.iter().map(|s| s.0 as i64).collect::<Vec<i64>>())
+                .execute(pool)
+                .await?;
+        }
+
+        if before != after {
+            AuditEntry {
+                guild_id: self.guild_id,
+                actor: *actor,
+                action: AuditAction::Permission,
+                target: format!("{} in {}", ty.as_display(), scope.mention()),
+                before: Some(before),
+                after: Some(after),
+            }
+            .record(pool)
+            .await?;
+        }
         Ok(())
     }
 }
 
+/// The channel followed by its parents, most specific first
+///
+/// Threads resolve to their channel and then its category
+pub fn channel_scopes(
+    ctx: &BotContext,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Vec<ChannelId> {
+    let mut scopes = vec![channel_id];
+    let mut current = channel_id;
+    // thread -> channel -> category is as deep as discord goes
+    for _ in 0..2 {
+        let parent = match ctx.cache.guild_channel(current) {
+            Some(channel) => channel.parent_id,
+            None => ctx
+                .cache
+                .guild_field(guild_id, |g| {
+                    g.threads
+                        .iter()
+                        .find(|t| t.id == current)
+                        .and_then(|t| t.parent_id)
+                })
+                .flatten(),
+        };
+        match parent {
+            Some(parent) => {
+                scopes.push(parent);
+                current = parent;
+            }
+            None => break,
+        }
+    }
+    scopes
+}
+
 pub struct PermissionsModule {
     guild_cache: RwLock<HashMap<GuildId, GuildPermissionEntry>>,
     pub sudo_users: RwLock<HashSet<UserId>>,
@@ -200,13 +837,28 @@ impl PermissionsModule {
     pub async fn initialize(
         self: Arc<Self>,
         mut task_rx: broadcast::Receiver<TaskMessage>,
+        owner_id: UserId,
     ) -> Result<()> {
-        // guild cache data
-        let rows = sqlx::query("select guild_id, type, overwrites, roles, users from Permissions")
+        // instance admins, the configured owner always being one
+        sqlx::query("insert into InstanceAdmins (id) values ($1) on conflict do nothing")
+            .bind(SqlId(owner_id))
+            .execute(&self.pool)
+            .await?;
+
+        let admins = sqlx::query("select id from InstanceAdmins")
+            .map(|row: PgRow| row.get::<SqlId<UserId>, _>("id").0)
             .fetch_all(&self.pool)
             .await?;
+        self.sudo_users.write().await.extend(admins);
+
+        // guild cache data
+        let rows = sqlx::query(
+            "select guild_id, type, overwrites, roles, users, deny_roles, deny_users from Permissions",
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-        for row in rows {
+        for row in &rows {
             self.guild_write(&row.get::<SqlId<GuildId>, _>("guild_id").0, |entry| {
                 let data = unsafe { entry.get_mut(&row.get::<PermissionType, _>("type")) };
                 data.discord =
@@ -222,6 +874,65 @@ impl PermissionsModule {
                     .iter()
                     .map(|s| UserId(*s as u64))
                     .collect::<Vec<UserId>>();
+                data.deny_roles = row
+                    .get::<Vec<i64>, _>("deny_roles")
+                    .iter()
+                    .map(|s| RoleId(*s as u64))
+                    .collect::<Vec<RoleId>>();
+                data.deny_users = row
+                    .get::<Vec<i64>, _>("deny_users")
+                    .iter()
+                    .map(|s| UserId(*s as u64))
+                    .collect::<Vec<UserId>>();
+            })
+            .await;
+        }
+
+        // scoped overrides
+        let rows =
+            sqlx::query("select guild_id, type, scope, roles, users, deny_roles, deny_users from PermissionOverrides")
+                .fetch_all(&self.pool)
+                .await?;
+
+        for row in &rows {
+            self.guild_write(&row.get::<SqlId<GuildId>, _>("guild_id").0, |entry| {
+                let data = unsafe { entry.get_mut(&row.get::<PermissionType, _>("type")) };
+                data.scopes.insert(
+                    row.get::<SqlId<ChannelId>, _>("scope").0,
+                    ScopedPermissionData {
+                        roles: row
+                            .get::<Vec<i64>, _>("roles")
+                            .iter()
+                            .map(|s| RoleId(*s as u64))
+                            .collect::<Vec<RoleId>>(),
+                        users: row
+                            .get::<Vec<i64>, _>("users")
+                            .iter()
+                            .map(|s| UserId(*s as u64))
+                            .collect::<Vec<UserId>>(),
+                        deny_roles: row
+                            .get::<Vec<i64>, _>("deny_roles")
+                            .iter()
+                            .map(|s| RoleId(*s as u64))
+                            .collect::<Vec<RoleId>>(),
+                        deny_users: row
+                            .get::<Vec<i64>, _>("deny_users")
+                            .iter()
+                            .map(|s| UserId(*s as u64))
+                            .collect::<Vec<UserId>>(),
+                    },
+                );
+            })
+            .await;
+        }
+
+        let rows = sqlx::query("select guild_id, keep_user_grants from PermissionSettings")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for row in &rows {
+            self.guild_write(&row.get::<SqlId<GuildId>, _>("guild_id").0, |entry| {
+                entry.keep_user_grants = row.get("keep_user_grants");
             })
             .await;
         }
@@ -242,135 +953,468 @@ impl PermissionsModule {
         Ok(())
     }
 
-    fn entry_perms_check(
-        ty: &PermissionType,
-        entry: &GuildPermissionEntry,
+    pub async fn export_config(&self, guild_id: &GuildId) -> BTreeMap<String, PermissionSection> {
+        self.guild_read(guild_id, |entry| {
+            entry
+                .data
+                .iter()
+                .map(|(ty, data)| (s!(ty.as_value()), data.to_section()))
+                .collect()
+        })
+        .await
+    }
+
+    /// Adds the default entry for every permission a configuration leaves out
+    pub fn fill_config(config: &mut BTreeMap<String, PermissionSection>) {
+        for ty in PermissionType::all() {
+            config
+                .entry(s!(ty.as_value()))
+                .or_insert_with(|| GuildPermissionData::default(ty).to_section());
+        }
+    }
+
+    pub async fn import_config(
+        &self,
+        conn: &mut PgConnection,
+        guild_id: &GuildId,
+        config: &BTreeMap<String, PermissionSection>,
+        keep_user_grants: bool,
+    ) -> Result<()> {
+        // grants from the file are permanent, an old expiry must not revoke them later
+        sqlx::query("delete from PermissionExpiries where guild_id = $1")
+            .bind(SqlId(*guild_id))
+            .execute(&mut *conn)
+            .await?;
+
+        let mut entry = GuildPermissionEntry::new(guild_id);
+        for (ty, section) in config {
+            let ty = PermissionType::from_string(ty)?;
+            entry
+                .data
+                .insert(ty, GuildPermissionData::from_section(section));
+            entry.save_in(&ty, &mut *conn).await?;
+            entry.save_scopes_in(&ty, &mut *conn).await?;
+        }
+        Self::save_keep_user_grants(conn, guild_id, keep_user_grants).await
+    }
+
+    /// How many grants are still waiting to expire, an import drops these
+    pub async fn pending_expiries(pool: &PgPool, guild_id: &GuildId) -> Result<i64> {
+        Ok(
+            sqlx::query("select count(*) as count from PermissionExpiries where guild_id = $1")
+                .bind(SqlId(*guild_id))
+                .map(|row: PgRow| row.get::<i64, _>("count"))
+                .fetch_one(pool)
+                .await?,
+        )
+    }
+
+    /// Puts an imported configuration in the cache, once it's been saved
+    pub async fn cache_config(
+        &self,
+        guild_id: &GuildId,
+        config: &BTreeMap<String, PermissionSection>,
+        keep_user_grants: bool,
+    ) {
+        self.guild_write(guild_id, |entry| {
+            for (ty, section) in config {
+                if let Ok(ty) = PermissionType::from_string(ty) {
+                    entry
+                        .data
+                        .insert(ty, GuildPermissionData::from_section(section));
+                }
+            }
+            entry.keep_user_grants = keep_user_grants;
+        })
+        .await
+    }
+
+    // checks every kind of rule of an entry, taking bits into account only for allows
+    fn entry_match(
+        data: &GuildPermissionData,
         highest: &DiscordPermissions,
         user: &UserId,
         roles: &[Role],
-    ) -> bool {
-        let data = entry.get(ty);
-        (data.discord.bits() > 0 && highest.contains(data.discord))
-            || data.users.contains(user)
-            || roles.iter().any(|item| data.roles.contains(&item.id))
+        scopes: &[ChannelId],
+        grant: Grant,
+    ) -> EntryMatch {
+        let mut found = EntryMatch::default();
+        if grant == Grant::Allow && data.discord.bits() > 0 && highest.contains(data.discord) {
+            found.bits = Some(RuleMatch::Bits(data.discord));
+        }
+        let (rule_roles, rule_users) = data.lists(grant);
+        if rule_users.contains(user) {
+            found.user = Some(RuleMatch::User(*user));
+        }
+        if let Some(role) = roles.iter().find(|item| rule_roles.contains(&item.id)) {
+            found.role = Some(RuleMatch::Role(role.id));
+        }
+        for scope in scopes {
+            if let Some(scoped) = data.scopes.get(scope) {
+                let (rule_roles, rule_users) = scoped.lists(grant);
+                if found.user.is_none() && rule_users.contains(user) {
+                    found.user = Some(RuleMatch::ScopedUser(*scope, *user));
+                }
+                if found.role.is_none() {
+                    if let Some(role) = roles.iter().find(|item| rule_roles.contains(&item.id)) {
+                        found.role = Some(RuleMatch::ScopedRole(*scope, role.id));
+                    }
+                }
+            }
+        }
+        found
     }
 
-    #[allow(clippy::needless_lifetimes)] // lifetimes not actually needless
-    pub async fn check<'a>(
+    /// Runs the permission logic, recording every step along the way
+    pub async fn evaluate(
         &self,
-        ty: &'a PermissionType,
+        ty: &PermissionType,
         guild: &GuildId,
         owner: &UserId,
         user: &UserId,
         roles: &[Role],
-    ) -> Option<&'a PermissionType> {
-        if self.sudo_users.read().await.contains(owner) {
-            return None;
+        scopes: &[ChannelId],
+    ) -> PermissionTrace {
+        let mut steps = Vec::new();
+
+        let is_owner = owner == user;
+        steps.push((s!("Owner bypass"), s!(if is_owner { "Yes" } else { "No" })));
+        if is_owner {
+            return PermissionTrace {
+                steps,
+                allowed: true,
+            };
         }
 
-        if owner == user {
-            return None;
+        let sudo = self.sudo_users.read().await.contains(user);
+        steps.push((s!("Sudo bypass"), s!(if sudo { "Yes" } else { "No" })));
+        if sudo {
+            return PermissionTrace {
+                steps,
+                allowed: true,
+            };
         }
+
         let mut highest_permissions = DiscordPermissions::empty();
         for role in roles {
             highest_permissions.insert(role.permissions);
         }
-        self.guild_read(guild, |entry| {
-            if Self::entry_perms_check(
-                &PermissionType::Administrator,
-                entry,
-                &highest_permissions,
-                user,
-                roles,
-            ) || Self::entry_perms_check(ty, entry, &highest_permissions, user, roles)
-            {
-                None
-            } else {
-                Some(ty)
-            }
-        })
-        .await
-    }
 
-    pub async fn permissions_list(
-        &self,
-        ctx: &BotContext,
-        interaction: &ApplicationCommandInteraction,
-    ) -> Result<()> {
-        defer_command(&ctx, interaction).await?;
-        interaction
-            .create_followup_message(&ctx.http, |a| {
-                a.components(|b| {
-                    b.create_action_row(|c| {
-                        c.create_select_menu(|d| {
-                            d.custom_id(build_custom_id(&CustomIdType::ListPermissions, &None))
-                                .options(|e| {
-                                    PermissionType::build_select_menu_options(e);
-                                    e
-                                })
-                        })
-                    })
-                })
-                .embed(|b| b.description("Select a permission"))
-            })
-            .await?;
+        let (entry_steps, allowed) = self
+            .guild_read(guild, |entry| {
+                let mut steps = Vec::new();
+                let administrator = &PermissionType::Administrator;
 
-        Ok(())
-    }
+                // denies win over any allow, including the administrator one
+                let denied = Self::entry_match(
+                    entry.get(ty),
+                    &highest_permissions,
+                    user,
+                    roles,
+                    scopes,
+                    Grant::Deny,
+                );
+                steps.extend(denied.steps(&format!("{} deny", ty.as_display()), Grant::Deny));
+                if denied.is_match() {
+                    return (steps, false);
+                }
 
-    pub async fn permissions_list_component(
-        &self,
-        ctx: &BotContext,
-        interaction: &MessageComponentInteraction,
-    ) -> Result<()> {
-        defer_component(&ctx, interaction).await?;
-        let ty =
-            PermissionType::from_string(interaction.data.values.get(0).ok_or_else(|| {
-                BotError::InvalidRequest("Missing component values".to_string())
-            })?)?;
+                let administrator_denied = Self::entry_match(
+                    entry.get(administrator),
+                    &highest_permissions,
+                    user,
+                    roles,
+                    scopes,
+                    Grant::Deny,
+                );
+                steps.extend(administrator_denied.steps("Administrator deny", Grant::Deny));
+                if !administrator_denied.is_match() {
+                    let administrator_allowed = Self::entry_match(
+                        entry.get(administrator),
+                        &highest_permissions,
+                        user,
+                        roles,
+                        scopes,
+                        Grant::Allow,
+                    );
+                    steps.extend(administrator_allowed.steps("Administrator", Grant::Allow));
+                    if administrator_allowed.is_match() {
+                        return (steps, true);
+                    }
+                }
 
-        let (permissions, roles, users) = self
-            .guild_read(&interaction.guild_id.ok_or(BotError::GuildOnly)?, |entry| {
-                let data = entry.get(&ty);
-                (
-                    data.discord.to_string(),
-                    data.roles
-                        .iter()
-                        .map(|x| x.mention().to_string())
-                        .collect::<Vec<String>>()
-                        .join("\n"),
-                    data.users
-                        .iter()
-                        .map(|x| x.mention().to_string())
-                        .collect::<Vec<String>>()
-                        .join("\n"),
-                )
+                let allowed = Self::entry_match(
+                    entry.get(ty),
+                    &highest_permissions,
+                    user,
+                    roles,
+                    scopes,
+                    Grant::Allow,
+                );
+                steps.extend(allowed.steps(ty.as_display(), Grant::Allow));
+                (steps, allowed.is_match())
             })
             .await;
 
-        interaction
-            .edit_original_interaction_response(&ctx.http, |a| {
-                a.embed(|b| {
-                    if !roles.is_empty() {
-                        b.field("Roles", roles, false);
-                    }
-                    if !users.is_empty() {
-                        b.field("Users", users, false);
-                    }
-                    b.field("Default", permissions, false)
-                        .title(ty.as_display())
-                })
-                .components(|b| {
-                    b.create_action_row(|c| {
-                        c.create_select_menu(|d| {
-                            d.custom_id(build_custom_id(&CustomIdType::ListPermissions, &None))
-                                .options(|e| {
-                                    PermissionType::build_select_menu_options(e);
-                                    e
-                                })
-                                .placeholder(ty.as_display())
+        steps.extend(entry_steps);
+        PermissionTrace { steps, allowed }
+    }
+
+    #[allow(clippy::needless_lifetimes)] // lifetimes not actually needless
+    pub async fn check<'a>(
+        &self,
+        ty: &'a PermissionType,
+        guild: &GuildId,
+        owner: &UserId,
+        user: &UserId,
+        roles: &[Role],
+        scopes: &[ChannelId],
+    ) -> Option<&'a PermissionType> {
+        if self
+            .evaluate(ty, guild, owner, user, roles, scopes)
+            .await
+            .allowed
+        {
+            None
+        } else {
+            Some(ty)
+        }
+    }
+
+    pub async fn permissions_check(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let ty = PermissionType::from_string(&args.get_string("permission")?)?;
+        let user = args.get_user("user")?.get_user().clone();
+        let channel = args
+            .get_channel("channel")
+            .map(|c| c.id)
+            .unwrap_or(interaction.channel_id);
+
+        let member = guild_id.member(&ctx, user.id).await?;
+        let (owner, roles) = ctx
+            .cache
+            .guild_field(guild_id, |g| {
+                (
+                    g.owner_id,
+                    member
+                        .roles
+                        .iter()
+                        .filter_map(|role| g.roles.get(role).cloned())
+                        .collect::<Vec<Role>>(),
+                )
+            })
+            .ok_or(BotError::CacheMissing)?;
+        let scopes = channel_scopes(ctx, guild_id, channel);
+
+        let trace = self
+            .evaluate(&ty, &guild_id, &owner, &user.id, &roles, &scopes)
+            .await;
+
+        interaction
+            .create_followup_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.title(format!("{} for {}", ty.as_display(), user.tag()))
+                        .description(
+                            trace
+                                .steps
+                                .iter()
+                                .map(|(step, result)| format!("**{}**: {}", step, result))
+                                .collect::<Vec<String>>()
+                                .join("\n"),
+                        )
+                        .field("Channel", channel.mention(), true)
+                        .field(
+                            "Verdict",
+                            if trace.allowed { "Allowed" } else { "Denied" },
+                            true,
+                        )
+                        .color(if trace.allowed {
+                            Color::DARK_GREEN
+                        } else {
+                            Color::RED
                         })
-                    })
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn instance_admin_add(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        let user = args.get_user("user")?.get_user().clone();
+        if user.bot {
+            return Err(Error::new(BotError::Generic(s!(
+                "Bots can't be instance admins"
+            ))));
+        }
+
+        if !self.sudo_users.write().await.insert(user.id) {
+            return Err(Error::new(BotError::Generic(s!(
+                "User is already an instance admin"
+            ))));
+        }
+        sqlx::query(
+            "insert into InstanceAdmins (id, added_by) values ($1, $2) on conflict do nothing",
+        )
+        .bind(SqlId(user.id))
+        .bind(SqlId(interaction.user.id))
+        .execute(&self.pool)
+        .await?;
+        info!(
+            "{} added {} as an instance admin",
+            interaction.user.id, user.id
+        );
+
+        FollowupBuilder::new()
+            .description(format!("{} is now an instance admin", user.mention()))
+            .ephemeral()
+            .build_command_response(&ctx, interaction)
+            .await
+    }
+
+    pub async fn instance_admin_remove(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+        owner_id: UserId,
+    ) -> Result<()> {
+        let user = args.get_user("user")?.get_user().clone();
+        // the owner gets seeded back in on every start anyway
+        if user.id == owner_id {
+            return Err(Error::new(BotError::Generic(s!(
+                "The bot owner is always an instance admin"
+            ))));
+        }
+
+        if !self.sudo_users.write().await.remove(&user.id) {
+            return Err(Error::new(BotError::Generic(s!(
+                "User is not an instance admin"
+            ))));
+        }
+        sqlx::query("delete from InstanceAdmins where id = $1")
+            .bind(SqlId(user.id))
+            .execute(&self.pool)
+            .await?;
+        info!(
+            "{} removed {} as an instance admin",
+            interaction.user.id, user.id
+        );
+
+        FollowupBuilder::new()
+            .description(format!("{} is no longer an instance admin", user.mention()))
+            .ephemeral()
+            .build_command_response(&ctx, interaction)
+            .await
+    }
+
+    pub async fn instance_admin_list(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+    ) -> Result<()> {
+        let mut admins = self
+            .sudo_users
+            .read()
+            .await
+            .iter()
+            .copied()
+            .collect::<Vec<UserId>>();
+        admins.sort();
+
+        FollowupBuilder::new()
+            .title("Instance admins")
+            .description(
+                admins
+                    .iter()
+                    .map(|user| user.mention().to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            )
+            .ephemeral()
+            .build_command_response(&ctx, interaction)
+            .await
+    }
+
+    fn build_list_page(entry: &GuildPermissionEntry, page: usize) -> (CreateEmbed, bool) {
+        let types = PermissionType::all();
+        let pages = types.len().div_ceil(LIST_PAGE_SIZE);
+        let page = page.min(pages - 1);
+
+        let mut embed = CreateEmbed::default();
+        embed
+            .title("Permissions")
+            .footer(|f| f.text(format!("Page {}/{}", page + 1, pages)));
+        for ty in types
+            .iter()
+            .skip(page * LIST_PAGE_SIZE)
+            .take(LIST_PAGE_SIZE)
+        {
+            embed.field(
+                ty.as_display(),
+                format!("*{}*\n{}", ty.as_description(), entry.get(ty).summary()),
+                false,
+            );
+        }
+
+        (embed, page + 1 < pages)
+    }
+
+    pub async fn permissions_list(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+
+        let (embed, has_next) = self
+            .guild_read(&guild_id, |entry| Self::build_list_page(entry, 0))
+            .await;
+
+        interaction
+            .create_followup_message(&ctx.http, |m| {
+                m.add_embed(embed).components(|c| {
+                    create_page_buttons(c, &CustomIdType::PermissionsPage, 0, has_next)
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn permissions_list_component(
+        &self,
+        ctx: &BotContext,
+        interaction: &MessageComponentInteraction,
+        args: HashMap<String, String>,
+    ) -> Result<()> {
+        defer_component(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let page = args
+            .get("page")
+            .and_then(|p| usize::from_str(p).ok())
+            .unwrap_or(0);
+
+        let (embed, has_next) = self
+            .guild_read(&guild_id, |entry| Self::build_list_page(entry, page))
+            .await;
+
+        interaction
+            .edit_original_interaction_response(&ctx.http, |m| {
+                m.set_embed(embed).components(|c| {
+                    create_page_buttons(c, &CustomIdType::PermissionsPage, page as u64, has_next)
                 })
             })
             .await?;
@@ -386,15 +1430,15 @@ impl PermissionsModule {
     ) -> Result<()> {
         defer_command(&ctx, interaction).await?;
         let ty = PermissionType::from_string(&args.get_string("permission")?)?;
-        let permissions = DiscordPermissions::from_bits(args.get_integer("bits")? as u64)
-            .ok_or_else(|| BotError::InvalidRequest("Invalid permissions bits".to_string()))?;
+        let permissions =
+            parse_discord_permissions(&args.get_string("discord")?).map_err(BotError::Generic)?;
 
         self.write_guild_async(
             &interaction.guild_id.ok_or(BotError::GuildOnly)?,
             |entry: &mut GuildPermissionEntry, _| {
                 async move {
                     entry
-                        .set(&ty, &self.pool, |data| {
+                        .set(&ty, &self.pool, &interaction.user.id, |data| {
                             data.discord = permissions;
                         })
                         .await
@@ -405,16 +1449,365 @@ impl PermissionsModule {
         .await?;
 
         FollowupBuilder::new()
-            .description("Success")
+            .description(if permissions.is_empty() {
+                format!("{} no longer matches Discord permissions", ty.as_display())
+            } else {
+                format!(
+                    "{} now requires {}",
+                    ty.as_display(),
+                    permissions.get_permission_names().join(", ")
+                )
+            })
+            .build_command_followup(&ctx.http, interaction)
+            .await
+    }
+
+    pub async fn permissions_reset(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let ty = PermissionType::from_string(&args.get_string("permission")?)?;
+
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+
+        self.write_guild_async(&guild_id, |entry: &mut GuildPermissionEntry, _| {
+            async move {
+                entry
+                    .set(&ty, &self.pool, &interaction.user.id, |data| {
+                        *data = GuildPermissionData::default(&ty);
+                    })
+                    .await?;
+                entry.save_scopes(&ty, &self.pool).await
+            }
+            .boxed()
+        })
+        .await?;
+
+        // none of the grants they were for exist anymore
+        sqlx::query("delete from PermissionExpiries where guild_id = $1 and type = $2")
+            .bind(SqlId(guild_id))
+            .bind(ty)
+            .execute(&self.pool)
+            .await?;
+
+        FollowupBuilder::new()
+            .description(format!("{} reset to its defaults", ty.as_display()))
+            .build_command_followup(&ctx.http, interaction)
+            .await
+    }
+
+    pub async fn discord_autocomplete(
+        &self,
+        ctx: &BotContext,
+        interaction: &AutocompleteInteraction,
+        value: &str,
+    ) -> Result<()> {
+        // only the part after the last comma is being typed, the rest is kept as is
+        let (done, partial) = match value.rsplit_once(',') {
+            Some((done, partial)) => (
+                done.split(',')
+                    .map(|name| name.trim().to_ascii_lowercase())
+                    .filter(|name| !name.is_empty())
+                    .collect::<Vec<String>>(),
+                partial,
+            ),
+            None => (Vec::new(), value),
+        };
+        let partial = partial.trim().to_ascii_lowercase().replace([' ', '-'], "_");
+
+        let mut candidates = DISCORD_PERMISSION_NAMES
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| !done.iter().any(|done| done == name) && name.contains(&partial))
+            .collect::<Vec<&str>>();
+        // prefix matches first
+        candidates.sort_by_key(|name| !name.starts_with(&partial));
+
+        let choices = candidates
+            .into_iter()
+            .map(|name| {
+                let mut names = done.clone();
+                names.push(s!(name));
+                names.join(", ")
+            })
+            .filter(|choice| choice.len() <= MAX_CHOICE_LENGTH)
+            .take(MAX_CHOICES)
+            .collect::<Vec<String>>();
+
+        interaction
+            .create_autocomplete_response(&ctx.http, |r| {
+                for choice in &choices {
+                    r.add_string_choice(choice, choice);
+                }
+                r
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    // removes stale ids from a guild's entries, noting each change in the audit log
+    async fn prune<R, U>(
+        &self,
+        guild_id: &GuildId,
+        actor: &UserId,
+        stale_role: R,
+        stale_user: U,
+    ) -> Result<()>
+    where
+        R: FnMut(&RoleId) -> bool + Send,
+        U: FnMut(&UserId) -> bool + Send,
+    {
+        let mut stale_role = Some(stale_role);
+        let mut stale_user = Some(stale_user);
+        let pruned = self
+            .write_guild_async(guild_id, |entry: &mut GuildPermissionEntry, _| {
+                let stale_role = stale_role.take();
+                let stale_user = stale_user.take();
+                async move {
+                    match (stale_role, stale_user) {
+                        (Some(stale_role), Some(stale_user)) => {
+                            entry.prune(&self.pool, stale_role, stale_user).await
+                        }
+                        _ => Ok(Vec::new()),
+                    }
+                }
+                .boxed()
+            })
+            .await?;
+
+        for (ty, removed) in pruned {
+            AuditEntry {
+                guild_id: *guild_id,
+                actor: *actor,
+                action: AuditAction::PermissionPruned,
+                target: ty.as_display().to_string(),
+                before: Some(removed.join(" ")),
+                after: None,
+            }
+            .record(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn guild_data(&self, ctx: &BotContext, guild: &Guild) -> Result<()> {
+        // members only arrive in full for smaller guilds, don't mistake the rest for departures
+        let members_complete = guild.members.len() as u64 >= guild.member_count;
+        let prune_users = members_complete && !self.keep_user_grants(&guild.id).await;
+
+        self.prune(
+            &guild.id,
+            &ctx.cache.current_user_id(),
+            |role| !guild.roles.contains_key(role),
+            |user| prune_users && !guild.members.contains_key(user),
+        )
+        .await
+    }
+
+    pub async fn role_delete(
+        &self,
+        ctx: &BotContext,
+        guild_id: &GuildId,
+        role_id: &RoleId,
+    ) -> Result<()> {
+        self.prune(
+            guild_id,
+            &ctx.cache.current_user_id(),
+            |role| role == role_id,
+            |_| false,
+        )
+        .await
+    }
+
+    pub async fn member_removal(
+        &self,
+        ctx: &BotContext,
+        guild_id: &GuildId,
+        user: &User,
+    ) -> Result<()> {
+        if self.keep_user_grants(guild_id).await {
+            return Ok(());
+        }
+
+        self.prune(
+            guild_id,
+            &ctx.cache.current_user_id(),
+            |_| false,
+            |id| *id == user.id,
+        )
+        .await
+    }
+
+    pub async fn keep_user_grants(&self, guild_id: &GuildId) -> bool {
+        self.guild_read(guild_id, |entry| entry.keep_user_grants)
+            .await
+    }
+
+    /// Updates the setting, returning the previous value
+    pub async fn set_keep_user_grants(&self, guild_id: &GuildId, value: bool) -> Result<bool> {
+        let before = self
+            .guild_write(guild_id, |entry| {
+                std::mem::replace(&mut entry.keep_user_grants, value)
+            })
+            .await;
+
+        Self::save_keep_user_grants(&mut *self.pool.acquire().await?, guild_id, value).await?;
+        Ok(before)
+    }
+
+    async fn save_keep_user_grants(
+        conn: &mut PgConnection,
+        guild_id: &GuildId,
+        value: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            "insert into PermissionSettings (guild_id, keep_user_grants) values ($1, $2) \
+             on conflict on constraint permission_settings_idx do update set keep_user_grants = $2",
+        )
+        .bind(SqlId(*guild_id))
+        .bind(value)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn permissions_keep_user_grants(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let value = args.get_boolean("enabled")?;
+        let before = self.set_keep_user_grants(&guild_id, value).await?;
+
+        if before != value {
+            AuditEntry {
+                guild_id,
+                actor: interaction.user.id,
+                action: AuditAction::PermissionSetting,
+                target: s!("Keep user grants"),
+                before: Some(before.to_string()),
+                after: Some(value.to_string()),
+            }
+            .record(&self.pool)
+            .await?;
+        }
+
+        FollowupBuilder::new()
+            .description(if value {
+                "User grants are now kept when members leave"
+            } else {
+                "User grants are now removed when members leave"
+            })
             .build_command_followup(&ctx.http, interaction)
             .await
     }
 
+    // forgets pending expiries of grants that were changed by hand
+    async fn clear_expiries(
+        &self,
+        guild_id: &GuildId,
+        ty: &PermissionType,
+        scope: Option<ChannelId>,
+        grant: Grant,
+        role: Option<RoleId>,
+        user: Option<UserId>,
+    ) -> Result<()> {
+        sqlx::query("delete from PermissionExpiries where guild_id = $1 and type = $2 and scope is not distinct from $3 and deny = $4 and (role_id = $5 or user_id = $6)")
+            .bind(SqlId(*guild_id))
+            .bind(ty)
+            .bind(scope.map(SqlId))
+            .bind(grant == Grant::Deny)
+            .bind(role.map(SqlId))
+            .bind(user.map(SqlId))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Removes a grant whose time ran out, recording it in the audit log
+    #[allow(clippy::too_many_arguments)]
+    pub async fn expire_grant(
+        &self,
+        guild_id: &GuildId,
+        ty: &PermissionType,
+        scope: Option<ChannelId>,
+        grant: Grant,
+        role: Option<RoleId>,
+        user: Option<UserId>,
+        actor: &UserId,
+    ) -> Result<()> {
+        let removed = self
+            .write_guild_async(guild_id, |entry: &mut GuildPermissionEntry, _| {
+                async move {
+                    // database gets updated right below
+                    let data = unsafe { entry.get_mut(ty) };
+                    let (roles, users) = match scope {
+                        Some(scope) => match data.scopes.get_mut(&scope) {
+                            Some(scoped) => scoped.lists_mut(grant),
+                            None => return Ok(false),
+                        },
+                        None => data.lists_mut(grant),
+                    };
+                    let before = roles.len() + users.len();
+                    roles.retain(|x| Some(*x) != role);
+                    users.retain(|x| Some(*x) != user);
+                    if roles.len() + users.len() == before {
+                        return Ok(false);
+                    }
+
+                    match scope {
+                        Some(scope) => {
+                            if data.scopes.get(&scope).is_some_and(|s| s.is_empty()) {
+                                data.scopes.remove(&scope);
+                            }
+                            entry.save_scopes(ty, &self.pool).await?;
+                        }
+                        None => entry.save(ty, &self.pool).await?,
+                    }
+                    Ok::<bool, Error>(true)
+                }
+                .boxed()
+            })
+            .await?;
+
+        if removed {
+            let target = role
+                .map(|r| r.mention().to_string())
+                .or_else(|| user.map(|u| u.mention().to_string()))
+                .unwrap_or_default();
+            AuditEntry {
+                guild_id: *guild_id,
+                actor: *actor,
+                action: AuditAction::PermissionExpired,
+                target: match scope {
+                    Some(scope) => format!("{} in {}", ty.as_display(), scope.mention()),
+                    None => ty.as_display().to_string(),
+                },
+                before: Some(match grant {
+                    Grant::Allow => target,
+                    Grant::Deny => format!("Denied {}", target),
+                }),
+                after: None,
+            }
+            .record(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
     pub async fn permissions_add(
         &self,
         ctx: &BotContext,
         interaction: &ApplicationCommandInteraction,
         args: SlashMap,
+        grant: Grant,
     ) -> Result<()> {
         defer_command(&ctx, interaction).await?;
         let ty = PermissionType::from_string(&args.get_string("permission")?)?;
@@ -422,6 +1815,18 @@ impl PermissionsModule {
         let role_object = args.get_role("role").ok();
         let role_id = role_object.as_ref().map(|s| s.id);
         let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let scope = args.get_channel("scope").map(|s| s.id).ok();
+        let expires = match args.get_string("expires") {
+            Ok(s) => Some(
+                parse_duration(&s)
+                    .map_err(|e| BotError::Generic(e.to_string()))
+                    .and_then(|d| {
+                        chrono::Duration::from_std(d)
+                            .map_err(|_| BotError::Generic(s!("Expiry is too far away")))
+                    })?,
+            ),
+            Err(_) => None,
+        };
 
         if let Some(role) = role_object {
             if role.guild_id != guild_id {
@@ -435,25 +1840,75 @@ impl PermissionsModule {
             )));
         }
 
+        if let Some(scope) = scope {
+            if ctx.cache.guild_channel(scope).map(|c| c.guild_id) != Some(guild_id) {
+                return Err(Error::new(BotError::WrongGuild));
+            }
+        }
+
+        let add = |(roles, users): (&mut Vec<RoleId>, &mut Vec<UserId>)| {
+            if let Some(s) = user {
+                if !users.contains(&s) {
+                    users.push(s);
+                }
+            }
+            if let Some(s) = role_id {
+                if !roles.contains(&s) {
+                    roles.push(s);
+                }
+            }
+        };
+
         self.write_guild_async(&guild_id, |entry: &mut GuildPermissionEntry, _| {
             async move {
-                entry
-                    .set(&ty, &self.pool, |data| {
-                        if let Some(s) = user {
-                            data.users.push(s);
-                        }
-                        if let Some(s) = role_id {
-                            data.roles.push(s);
-                        }
-                    })
-                    .await
+                match scope {
+                    Some(scope) => {
+                        entry
+                            .set_scoped(&ty, scope, &self.pool, &interaction.user.id, |data| {
+                                add(data.lists_mut(grant))
+                            })
+                            .await
+                    }
+                    None => {
+                        entry
+                            .set(&ty, &self.pool, &interaction.user.id, |data| {
+                                add(data.lists_mut(grant))
+                            })
+                            .await
+                    }
+                }
             }
             .boxed()
         })
         .await?;
 
+        // adding again replaces any earlier expiry, making the grant permanent if none is given
+        self.clear_expiries(&guild_id, &ty, scope, grant, role_id, user)
+            .await?;
+        let expires_at = expires.map(|d| Utc::now() + d);
+        if let Some(expires_at) = expires_at {
+            for (role, user) in [(role_id, None), (None, user)] {
+                if role.is_none() && user.is_none() {
+                    continue;
+                }
+                sqlx::query("insert into PermissionExpiries (guild_id, type, scope, deny, role_id, user_id, expires_at) values ($1, $2, $3, $4, $5, $6, $7)")
+                    .bind(SqlId(guild_id))
+                    .bind(ty)
+                    .bind(scope.map(SqlId))
+                    .bind(grant == Grant::Deny)
+                    .bind(role.map(SqlId))
+                    .bind(user.map(SqlId))
+                    .bind(expires_at)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
         FollowupBuilder::new()
-            .description("Success")
+            .description(match expires_at {
+                Some(expires_at) => format!("Success, expires <t:{}:R>", expires_at.timestamp()),
+                None => s!("Success"),
+            })
             .build_command_followup(&ctx.http, interaction)
             .await
     }
@@ -463,11 +1918,13 @@ impl PermissionsModule {
         ctx: &BotContext,
         interaction: &ApplicationCommandInteraction,
         args: SlashMap,
+        grant: Grant,
     ) -> Result<()> {
         defer_command(&ctx, interaction).await?;
         let ty = PermissionType::from_string(&args.get_string("permission")?)?;
         let user = args.get_user("user").map(|s| s.get_user().id).ok();
         let role = args.get_role("role").map(|s| s.id).ok();
+        let scope = args.get_channel("scope").map(|s| s.id).ok();
 
         if let (None, None) = (user, role) {
             return Err(Error::new(BotError::Generic(
@@ -475,41 +1932,25 @@ impl PermissionsModule {
             )));
         }
 
-        let mut user_found = true;
-        let mut role_found = true;
-        self.write_guild_async(
-            &interaction.guild_id.ok_or(BotError::GuildOnly)?,
-            |entry: &mut GuildPermissionEntry, _| {
-                async move {
-                    entry
-                        .set(&ty, &self.pool, |data| {
-                            if let Some(s) = user {
-                                match data.users.binary_search(&s) {
-                                    Ok(s) => {
-                                        data.users.remove(s);
-                                    }
-                                    Err(_) => {
-                                        user_found = false;
-                                    }
-                                }
-                            }
-                            if let Some(s) = role {
-                                match data.roles.binary_search(&s) {
-                                    Ok(s) => {
-                                        data.roles.remove(s);
-                                    }
-                                    Err(_) => {
-                                        role_found = false;
-                                    }
-                                }
-                            }
-                        })
-                        .await
-                }
-                .boxed()
-            },
-        )
-        .await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+
+        // make sure everything exists before touching anything
+        let (user_found, role_found) = self
+            .guild_read(&guild_id, |entry| {
+                let data = entry.get(&ty);
+                let (roles, users) = match scope {
+                    Some(scope) => match data.scopes.get(&scope) {
+                        Some(scoped) => scoped.lists(grant),
+                        None => return (user.is_none(), role.is_none()),
+                    },
+                    None => data.lists(grant),
+                };
+                (
+                    user.map(|s| users.contains(&s)).unwrap_or(true),
+                    role.map(|s| roles.contains(&s)).unwrap_or(true),
+                )
+            })
+            .await;
 
         if !user_found {
             return Err(Error::new(BotError::Generic(format!(
@@ -525,6 +1966,40 @@ impl PermissionsModule {
             ))));
         }
 
+        let remove = |(roles, users): (&mut Vec<RoleId>, &mut Vec<UserId>)| {
+            if let Some(s) = user {
+                users.retain(|x| *x != s);
+            }
+            if let Some(s) = role {
+                roles.retain(|x| *x != s);
+            }
+        };
+
+        self.write_guild_async(&guild_id, |entry: &mut GuildPermissionEntry, _| {
+            async move {
+                match scope {
+                    Some(scope) => {
+                        entry
+                            .set_scoped(&ty, scope, &self.pool, &interaction.user.id, |data| {
+                                remove(data.lists_mut(grant))
+                            })
+                            .await
+                    }
+                    None => {
+                        entry
+                            .set(&ty, &self.pool, &interaction.user.id, |data| {
+                                remove(data.lists_mut(grant))
+                            })
+                            .await
+                    }
+                }
+            }
+            .boxed()
+        })
+        .await?;
+        self.clear_expiries(&guild_id, &ty, scope, grant, role, user)
+            .await?;
+
         FollowupBuilder::new()
             .description("Success")
             .build_command_followup(&ctx.http, interaction)
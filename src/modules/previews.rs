@@ -3,49 +3,128 @@
 // You should have received a copy of the license along with this program
 // If not, see <https://www.gnu.org/licenses/#AGPL>
 
+use crate::custom_ids::{build_custom_id, CustomIdType};
 use crate::decode::SlashMap;
 use crate::impl_cache_functions;
+use crate::modules::audit::{AuditAction, AuditEntry};
+use crate::modules::config::PreviewSection;
 use crate::prelude::*;
 use crate::tasks::TaskMessage;
 use crate::utils::{
-    default_arg, defer_command, link_guild, remove_indexes, BotContext, FollowupBuilder, Link,
-    SqlId,
+    default_arg, defer_command, defer_component, link_guild, remove_indexes, BotContext,
+    FollowupBuilder, Link, SqlId,
 };
 use anyhow::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
 use regex::Regex;
-use serenity::builder::CreateEmbed;
+use serde_json::Value;
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::http::request::RequestBuilder;
+use serenity::http::routing::RouteInfo;
+use serenity::http::HttpError;
 use serenity::model::channel::{
-    Attachment, AttachmentType, Channel, GuildChannel, Message, MessageFlags, MessageType,
+    Attachment, AttachmentType, Channel, ChannelType, Embed, GuildChannel, Message, MessageFlags,
+    MessageType,
 };
-use serenity::model::guild::Guild;
+use serenity::model::event::MessageUpdateEvent;
+use serenity::model::guild::{Guild, Member};
 use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use serenity::model::prelude::component::ButtonStyle;
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
-use serenity::prelude::Mentionable;
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::webhook::Webhook;
+use serenity::model::Timestamp;
+use serenity::prelude::{Mentionable, SerenityError};
 use sqlx::postgres::PgRow;
-use sqlx::{PgPool, Row};
+use sqlx::{PgConnection, PgPool, Row};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use tokio::sync::{broadcast, RwLock};
 
+// attachments at or above this size can't be reuploaded
+const MAX_ATTACHMENT_SIZE: u64 = 8388246;
+const MAX_CONTENT_LENGTH: usize = 2000;
+const MAX_WEBHOOK_NAME_LENGTH: usize = 80;
+const WEBHOOK_NAME: &str = "Makita Previews";
+const SOURCE_DELETED: &str = "[source deleted]";
+// previews older than this lose their delete button, so PreviewMessages doesn't grow forever
+const PREVIEW_MESSAGE_DAYS: i64 = 30;
+
 #[derive(Default)]
 pub struct PreviewsConfig {
     pub auto_channels: Vec<ChannelId>,
+    // subset of auto_channels that preview through a webhook
+    pub webhook_channels: Vec<ChannelId>,
     pub archive_channel: Option<ChannelId>,
+    pub suppress_embeds: bool,
+    pub live: bool,
+}
+
+#[derive(Clone, Copy)]
+enum LiveKind {
+    Embed,
+    Webhook,
+    Archive,
+}
+
+impl LiveKind {
+    fn as_value(&self) -> &'static str {
+        match self {
+            Self::Embed => "embed",
+            Self::Webhook => "webhook",
+            Self::Archive => "archive",
+        }
+    }
+
+    fn from_value(from: &str) -> Result<Self> {
+        match from {
+            "embed" => Ok(Self::Embed),
+            "webhook" => Ok(Self::Webhook),
+            "archive" => Ok(Self::Archive),
+            _ => Err(Error::new(BotError::InvalidRequest(format!(
+                "Invalid live preview kind {}",
+                from
+            )))),
+        }
+    }
+}
+
+// a live preview or archive entry, and where it lives
+struct LivePreview {
+    message_id: MessageId,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    kind: LiveKind,
 }
 
 pub struct PreviewsModule {
     link_regex: Regex,
     cache: RwLock<HashMap<GuildId, PreviewsConfig>>,
+    webhooks: RwLock<HashMap<ChannelId, Webhook>>,
+    // source messages with live previews, so unrelated edits skip the database
+    // along with their last seen edit, since updates are also sent for things that aren't edits
+    live_sources: RwLock<HashMap<MessageId, Option<Timestamp>>>,
+    // messages with rows in PreviewMessages or LivePreviews, so unrelated deletes skip the database
+    // along with their guild, so they can be dropped when it goes
+    preview_messages: RwLock<HashMap<MessageId, GuildId>>,
+    // who gets told about unsupported messages, if anyone
+    report_to: Option<UserId>,
+    reported: RwLock<HashSet<String>>,
 }
 
 impl PreviewsModule {
-    pub fn new() -> Result<Self> {
+    pub fn new(report_to: Option<UserId>) -> Result<Self> {
         Ok(Self {
             link_regex: Regex::new(
                 r"https://(?:\w+\.)?discord(?:app)?.com/channels/(\d+)/(\d+)/(\d+)",
             )?,
             cache: Default::default(),
+            webhooks: Default::default(),
+            live_sources: Default::default(),
+            preview_messages: Default::default(),
+            report_to,
+            reported: Default::default(),
         })
     }
 }
@@ -67,11 +146,12 @@ impl PreviewsModule {
         pool: &PgPool,
     ) -> Result<()> {
         // load auto channels from db
-        let rows = sqlx::query("select guild_id, channel_id from PreviewChannels")
+        let rows = sqlx::query("select guild_id, channel_id, webhook from PreviewChannels")
             .map(|row: PgRow| {
                 (
                     row.get::<SqlId<GuildId>, _>("guild_id").0,
                     row.get::<SqlId<ChannelId>, _>("channel_id").0,
+                    row.get::<bool, _>("webhook"),
                 )
             })
             .fetch_all(pool)
@@ -81,6 +161,9 @@ impl PreviewsModule {
             instance
                 .write_cache(&row.0, |data| {
                     data.auto_channels.push(row.1);
+                    if row.2 {
+                        data.webhook_channels.push(row.1);
+                    }
                 })
                 .await;
         }
@@ -104,7 +187,54 @@ impl PreviewsModule {
                 .await;
         }
 
+        // load settings from db
+        let rows = sqlx::query("select guild_id, suppress_embeds, live from PreviewSettings")
+            .map(|row: PgRow| {
+                (
+                    row.get::<SqlId<GuildId>, _>("guild_id").0,
+                    row.get::<bool, _>("suppress_embeds"),
+                    row.get::<bool, _>("live"),
+                )
+            })
+            .fetch_all(pool)
+            .await?;
+
+        for row in rows {
+            instance
+                .write_cache(&row.0, |data| {
+                    data.suppress_embeds = row.1;
+                    data.live = row.2;
+                })
+                .await;
+        }
+
+        // load live preview sources from db
+        let sources = sqlx::query("select distinct source_message_id from LivePreviews")
+            .map(|row: PgRow| row.get::<SqlId<MessageId>, _>("source_message_id").0)
+            .fetch_all(pool)
+            .await?;
+        instance
+            .live_sources
+            .write()
+            .await
+            .extend(sources.into_iter().map(|source| (source, None)));
+
+        // load preview messages from db
+        let messages = sqlx::query(
+            "select message_id, guild_id from PreviewMessages union select message_id, guild_id from LivePreviews",
+        )
+        .map(|row: PgRow| {
+            (
+                row.get::<SqlId<MessageId>, _>("message_id").0,
+                row.get::<SqlId<GuildId>, _>("guild_id").0,
+            )
+        })
+        .fetch_all(pool)
+        .await?;
+        instance.preview_messages.write().await.extend(messages);
+
         // task event handling
+        let pool = pool.clone();
         tokio::spawn(async move {
             loop {
                 let msg = task_rx.recv().await;
@@ -112,6 +242,20 @@ impl PreviewsModule {
                     Ok(TaskMessage::Kill) | Err(_) => break,
                     Ok(TaskMessage::DestroyGuild(g)) => {
                         instance.cache.write().await.remove(&g);
+                        instance
+                            .preview_messages
+                            .write()
+                            .await
+                            .retain(|_, guild| *guild != g);
+                        // normally gone with the guild already, but none of it should outlive it
+                        for query in [
+                            "delete from PreviewMessages where guild_id = $1",
+                            "delete from LivePreviews where guild_id = $1",
+                        ] {
+                            if let Err(e) = sqlx::query(query).bind(SqlId(g)).execute(&pool).await {
+                                error!("Error removing previews of guild {}: {:?}", g, e);
+                            }
+                        }
                     }
                 }
             }
@@ -120,7 +264,203 @@ impl PreviewsModule {
         Ok(())
     }
 
+    fn set_author(embed: &mut CreateEmbed, message: &Message, flags: MessageFlags) {
+        embed.author(|author| {
+            author
+                .name(match message.author.discriminator {
+                    0 => message.author.name.clone(),
+                    _ => format!(
+                        "{}#{:04}",
+                        message.author.name, message.author.discriminator
+                    ),
+                })
+                .url(if flags.contains(MessageFlags::IS_CROSSPOST) {
+                    message.message_reference.as_ref().unwrap().link()
+                } else {
+                    message.link()
+                })
+                .icon_url(
+                    message
+                        .author
+                        .avatar_url()
+                        .unwrap_or_else(|| message.author.default_avatar_url()),
+                )
+        });
+    }
+
+    // logs a message we can't render, and tells the bot owner about each kind once
+    async fn report_unsupported(&self, ctx: &BotContext, message: &Message, kind: String) {
+        warn!(
+            "unsupported message type {} in channel {} ({})",
+            kind,
+            message.channel_id,
+            message.link()
+        );
+
+        let owner = match self.report_to {
+            Some(owner) => owner,
+            None => return,
+        };
+        if !self.reported.write().await.insert(kind.clone()) {
+            return;
+        }
+        let result = async {
+            owner
+                .create_dm_channel(ctx)
+                .await?
+                .say(
+                    ctx,
+                    format!(
+                        "Unsupported message type {} spotted: {}",
+                        kind,
+                        message.link()
+                    ),
+                )
+                .await
+        }
+        .await;
+        if let Err(err) = result {
+            error!("Error notifying owner of unsupported message: {:?}", err);
+        }
+    }
+
+    // newer types deserialize as Unknown, and polls look like empty regular messages
+    fn needs_raw(message: &Message) -> bool {
+        message.kind == MessageType::Unknown
+            || (matches!(
+                message.kind,
+                MessageType::Regular | MessageType::InlineReply
+            ) && message.content.is_empty()
+                && message.embeds.is_empty()
+                && message.attachments.is_empty()
+                && message.sticker_items.is_empty())
+    }
+
+    async fn raw_message(ctx: &BotContext, message: &Message) -> Result<Value> {
+        Ok(ctx
+            .http
+            .fire(
+                RequestBuilder::new(RouteInfo::GetMessage {
+                    channel_id: message.channel_id.0,
+                    message_id: message.id.0,
+                })
+                .build(),
+            )
+            .await?)
+    }
+
+    // renders message types serenity has no variant for, from the raw message json
+    fn describe_newer(message: &Message, raw: &Value, maybe_link_foreign: &str) -> Option<String> {
+        let author = message.author.mention();
+        let server = match maybe_link_foreign {
+            "" => "this server",
+            foreign => foreign.trim(),
+        };
+        // auto moderation and poll results keep their details in embed fields
+        let embed_field = |name: &str| {
+            message.embeds.first().and_then(|embed| {
+                embed
+                    .fields
+                    .iter()
+                    .find(|field| field.name == name)
+                    .map(|field| field.value.clone())
+            })
+        };
+
+        match raw["type"].as_u64()? {
+            // poll
+            0 | 19 => {
+                let poll = raw.get("poll")?;
+                let mut description = format!("**{}**", poll["question"]["text"].as_str()?);
+                for answer in poll["answers"].as_array()? {
+                    if let Some(text) = answer["poll_media"]["text"].as_str() {
+                        description.push_str(&format!("\n• {}", text));
+                    }
+                }
+                if let Some(expiry) = poll["expiry"]
+                    .as_str()
+                    .and_then(|expiry| DateTime::parse_from_rfc3339(expiry).ok())
+                {
+                    description.push_str(&format!("\nEnds <t:{}:R>", expiry.timestamp()));
+                }
+                Some(description)
+            }
+            // auto moderation action
+            24 => {
+                let blocked = message
+                    .embeds
+                    .first()
+                    .and_then(|embed| embed.description.clone())
+                    .unwrap_or_default();
+                Some(match embed_field("rule_name") {
+                    Some(rule) => format!(
+                        "AutoMod blocked a message from {} for breaking **{}**\n>>> {}",
+                        author, rule, blocked
+                    ),
+                    None => format!("AutoMod blocked a message from {}\n>>> {}", author, blocked),
+                })
+            }
+            // role subscription purchase
+            25 => {
+                let data = raw.get("role_subscription_data")?;
+                let months = data["total_months_subscribed"].as_u64().unwrap_or(1);
+                Some(format!(
+                    "{} {} **{}** and has been a subscriber of {} for {} month{}",
+                    author,
+                    if data["is_renewal"].as_bool().unwrap_or(false) {
+                        "renewed"
+                    } else {
+                        "joined"
+                    },
+                    data["tier_name"].as_str()?,
+                    server,
+                    months,
+                    if months == 1 { "" } else { "s" }
+                ))
+            }
+            // stage events
+            27 => Some(format!("{} started **{}**", author, message.content)),
+            28 => Some(format!("{} ended **{}**", author, message.content)),
+            29 => Some(format!("{} is now a speaker", author)),
+            30 => Some(format!("{} raised their hand", author)),
+            31 => Some(format!(
+                "{} changed the stage topic to **{}**",
+                author, message.content
+            )),
+            // guild application premium subscription
+            32 => Some(format!(
+                "{} upgraded {} to premium for {}",
+                author,
+                match &message.application {
+                    Some(application) => format!("**{}**", application.name),
+                    None => s!("an app"),
+                },
+                server
+            )),
+            // poll result
+            46 => {
+                let question = embed_field("poll_question_text")?;
+                let total = embed_field("total_votes").unwrap_or_else(|| s!("0"));
+                Some(match embed_field("victor_answer_text") {
+                    Some(victor) => format!(
+                        "The poll **{}** has closed, **{}** won with {} of {} votes",
+                        question,
+                        victor,
+                        embed_field("victor_answer_votes").unwrap_or_else(|| s!("0")),
+                        total
+                    ),
+                    None => format!(
+                        "The poll **{}** has closed without a winner, with {} votes",
+                        question, total
+                    ),
+                })
+            }
+            _ => None,
+        }
+    }
+
     async fn derive_embed(
+        &self,
         ctx: &BotContext,
         message: &Message,
         foreign: Option<&Guild>,
@@ -154,39 +494,53 @@ impl PreviewsModule {
             GroupNameUpdate,
             GroupIconUpdate
         ) {
-            UserId(719046554744520754)
-                .create_dm_channel(&ctx)
-                .await
-                .unwrap()
-                .say(
-                    &ctx,
-                    format!("type {:?} spotted: {}", message.kind, message.link()),
-                )
-                .await
-                .unwrap();
+            self.report_unsupported(ctx, message, format!("{:?}", message.kind))
+                .await;
             embed.description("This is awkward... I shouldn't be able to see this message, yet I do. How will I resolve this paradox?");
             return embed;
         }
 
+        // serenity predates these types, so they're read from the raw message instead
+        if Self::needs_raw(message) {
+            let raw = match Self::raw_message(ctx, message).await {
+                Ok(raw) => raw,
+                Err(err) => {
+                    debug!("failed to fetch raw message {}: {:?}", message.id, err);
+                    Value::Null
+                }
+            };
+            if let Some(description) = Self::describe_newer(message, &raw, &maybe_link_foreign) {
+                Self::set_author(&mut embed, message, flags);
+                embed
+                    .description(description)
+                    .field("Channel", message.channel_id.mention(), true)
+                    .timestamp(message.timestamp);
+                if foreign.is_some() {
+                    embed.field("Guild", maybe_link_foreign.clone(), true);
+                }
+                return embed;
+            }
+            if message.kind == MessageType::Unknown {
+                let kind = match raw["type"].as_u64() {
+                    Some(kind) => format!("Unknown ({})", kind),
+                    None => s!("Unknown"),
+                };
+                self.report_unsupported(ctx, message, kind).await;
+                embed.description("Unsupported message type");
+                return embed;
+            }
+        }
+
         if filter_kind!(
-            Unknown,
             GuildInviteReminder,
             GuildDiscoveryGracePeriodInitialWarning,
             GuildDiscoveryGracePeriodFinalWarning,
             ThreadStarterMessage,
             ContextMenuCommand
         ) {
-            UserId(719046554744520754)
-                .create_dm_channel(&ctx)
-                .await
-                .unwrap()
-                .say(
-                    &ctx,
-                    format!("type {:?} spotted: {}", message.kind, message.link()),
-                )
-                .await
-                .unwrap();
-            embed.description("Unsupported message type. This has incident has been reported.");
+            self.report_unsupported(ctx, message, format!("{:?}", message.kind))
+                .await;
+            embed.description("Unsupported message type");
             return embed;
         }
 
@@ -205,27 +559,7 @@ impl PreviewsModule {
             ChatInputCommand,
             ContextMenuCommand
         ) {
-            embed.author(|author| {
-                author
-                    .name(match message.author.discriminator {
-                        0 => message.author.name.clone(),
-                        _ => format!(
-                            "{}#{:04}",
-                            message.author.name, message.author.discriminator
-                        ),
-                    })
-                    .url(if flags.contains(MessageFlags::IS_CROSSPOST) {
-                        message.message_reference.as_ref().unwrap().link()
-                    } else {
-                        message.link()
-                    })
-                    .icon_url(
-                        message
-                            .author
-                            .avatar_url()
-                            .unwrap_or_else(|| message.author.default_avatar_url()),
-                    )
-            });
+            Self::set_author(&mut embed, message, flags);
         }
 
         // standard-type messages
@@ -394,6 +728,183 @@ impl PreviewsModule {
         embed
     }
 
+    // threads only sit in the cache while they're active, anything else is fetched
+    async fn resolve_channel(
+        ctx: &BotContext,
+        guild: &Guild,
+        channel: ChannelId,
+    ) -> Result<GuildChannel> {
+        if let Some(found) = guild.channels.get(&channel) {
+            return match found {
+                Channel::Guild(g) => Ok(g.clone()),
+                _ => Err(Error::new(BotError::NotFound(s!("Channel")))),
+            };
+        }
+        if let Some(thread) = guild.threads.iter().find(|c| c.id == channel) {
+            return Ok(thread.clone());
+        }
+        match channel.to_channel(ctx).await {
+            Ok(Channel::Guild(fetched)) if fetched.guild_id == guild.id => Ok(fetched),
+            _ => Err(Error::new(BotError::NotFound(s!("Channel")))),
+        }
+    }
+
+    async fn can_read_history(
+        ctx: &BotContext,
+        guild: &Guild,
+        member: &Member,
+        channel: &GuildChannel,
+    ) -> Result<bool> {
+        if !matches!(
+            channel.kind,
+            ChannelType::NewsThread | ChannelType::PublicThread | ChannelType::PrivateThread
+        ) {
+            return Ok(guild
+                .user_permissions_in(channel, member)?
+                .read_message_history());
+        }
+
+        // threads have no overwrites of their own, access comes from the parent
+        let parent =
+            Self::resolve_channel(ctx, guild, channel.parent_id.ok_or(BotError::CacheMissing)?)
+                .await?;
+        let permissions = guild.user_permissions_in(&parent, member)?;
+        if !permissions.read_message_history() {
+            return Ok(false);
+        }
+        if channel.kind != ChannelType::PrivateThread || permissions.manage_threads() {
+            return Ok(true);
+        }
+
+        // private threads are only visible to the people in them
+        Ok(ctx
+            .http
+            .get_channel_thread_members(channel.id.0)
+            .await?
+            .iter()
+            .any(|thread_member| thread_member.user_id == Some(member.user.id)))
+    }
+
+    // checks the requester can see a message, then fetches it along with its guild
+    async fn fetch_message(
+        ctx: &BotContext,
+        from_user: &UserId,
+        guild: GuildId,
+        channel: ChannelId,
+        message: MessageId,
+    ) -> Result<(Message, Guild)> {
+        // get guild
+        let guild = ctx
+            .cache
+            .guild(guild)
+            .ok_or_else(|| BotError::NotFound(s!("Server")))?;
+        // get member
+        let member = guild.member(&ctx, *from_user).await.map_err(|_| {
+            BotError::Generic(s!("You must be in a server to preview messages from it"))
+        })?;
+        // get permissions
+        let target = Self::resolve_channel(ctx, &guild, channel).await?;
+        if !member
+            .roles(ctx)
+            .ok_or(BotError::CacheMissing)?
+            .iter()
+            .any(|role| role.permissions.administrator())
+            && !Self::can_read_history(ctx, &guild, &member, &target).await?
+        {
+            return Err(Error::new(BotError::Generic(s!(
+                "You do not have permission to view this message"
+            ))));
+        }
+        // get message
+        let mut message = channel
+            .message(&ctx, &message)
+            .await
+            .map_err(|_| Error::new(BotError::NotFound("Message".to_string())))?;
+        message.guild_id = Some(guild.id);
+
+        Ok((message, guild))
+    }
+
+    // copies embed data, since received embeds can't be sent back as they are
+    fn copy_embed(embed: Embed) -> CreateEmbed {
+        let mut builder = CreateEmbed::default();
+        if let Some(title) = &embed.title {
+            builder.title(title);
+        }
+        match embed.description {
+            Some(description) => builder.description(description),
+            None => builder.description("\u{200B}"),
+        };
+        if let Some(url) = &embed.url {
+            builder.url(url);
+        }
+        if let Some(timestamp) = &embed.timestamp {
+            builder.timestamp(timestamp.clone());
+        }
+        if let Some(image) = embed.image {
+            builder.image(image.url);
+        };
+        if let Some(thumbnail) = embed.thumbnail {
+            builder.thumbnail(thumbnail.url);
+        };
+        if let Some(color) = embed.colour {
+            builder.color(color);
+        };
+        if let Some(footer) = embed.footer {
+            builder.footer(|builder| {
+                builder.text(footer.text);
+                if let Some(icon_url) = &footer.icon_url {
+                    builder.icon_url(icon_url);
+                }
+                builder
+            });
+        };
+        if let Some(author) = embed.author {
+            builder.author(|builder| {
+                builder.name(author.name);
+                if let Some(url) = &author.url {
+                    builder.url(url);
+                }
+                if let Some(icon_url) = &author.icon_url {
+                    builder.icon_url(icon_url);
+                }
+                builder
+            });
+        };
+        for field in embed.fields {
+            builder.field(field.name, field.value, field.inline);
+        }
+        builder
+    }
+
+    async fn build_preview(
+        &self,
+        ctx: &BotContext,
+        message: Message,
+        guild: &Guild,
+        from_guild: &Option<GuildId>,
+    ) -> (Vec<CreateEmbed>, Vec<Attachment>) {
+        let embed = self
+            .derive_embed(
+                ctx,
+                &message,
+                from_guild.and_then(|s| if s == guild.id { None } else { Some(guild) }),
+            )
+            .await;
+
+        let mut embeds = vec![embed];
+        embeds.extend(message.embeds.into_iter().map(Self::copy_embed));
+
+        (
+            embeds,
+            message
+                .attachments
+                .into_iter()
+                .filter(|s| s.size < MAX_ATTACHMENT_SIZE)
+                .collect(),
+        )
+    }
+
     async fn preview(
         &self,
         ctx: &BotContext,
@@ -403,135 +914,252 @@ impl PreviewsModule {
         channel: ChannelId,
         message: MessageId,
     ) -> Result<(Vec<CreateEmbed>, Vec<Attachment>)> {
-        match ctx.cache.guild(guild) {
-            // get guild
-            None => Err(Error::new(BotError::NotFound("Server".to_string()))),
-            Some(guild) => {
-                match guild.member(&ctx, *from_user).await {
-                    // get member
-                    Err(_) => Err(Error::new(BotError::Generic(s!(
-                        "You must be in a server to preview messages from it"
-                    )))),
-                    Ok(member) => {
-                        // get permissions
-                        if !member
-                            .roles(ctx)
-                            .ok_or(BotError::CacheMissing)?
-                            .iter()
-                            .any(|role| role.permissions.administrator())
-                            && !guild
-                                .user_permissions_in(
-                                    match guild.channels.get(&channel) {
-                                        // get channel
-                                        Some(s) => match s {
-                                            Channel::Guild(g) => g,
-                                            _ => {
-                                                return Err(Error::new(BotError::NotFound(s!(
-                                                    "Channel"
-                                                ))))
-                                            }
-                                        },
-                                        None => {
-                                            match guild.threads.iter().find(|c| c.id == channel) {
-                                                Some(s) => s,
-                                                None => {
-                                                    return Err(Error::new(BotError::CacheMissing))
-                                                }
-                                            }
-                                        }
-                                    },
-                                    &member,
-                                )?
-                                .read_message_history()
-                        {
-                            return Err(Error::new(BotError::Generic(s!(
-                                "You do not have permission to view this message"
-                            ))));
-                        }
-                        // get message
-                        let mut message = channel
-                            .message(&ctx, &message)
-                            .await
-                            .map_err(|_| Error::new(BotError::NotFound("Message".to_string())))?;
-                        message.guild_id = Some(guild.id);
+        let (message, guild) = Self::fetch_message(ctx, from_user, guild, channel, message).await?;
+        Ok(self.build_preview(ctx, message, &guild, from_guild).await)
+    }
 
-                        // inner
-                        let embed = Self::derive_embed(
-                            ctx,
-                            &message,
-                            from_guild
-                                .and_then(|s| if s == guild.id { None } else { Some(&guild) }),
-                        )
-                        .await;
+    fn delete_button(components: &mut CreateComponents) -> &mut CreateComponents {
+        components.create_action_row(|row| {
+            row.create_button(|b| {
+                b.custom_id(build_custom_id(&CustomIdType::PreviewDelete, &None))
+                    .emoji('🗑')
+                    .style(ButtonStyle::Secondary)
+            })
+        })
+    }
 
-                        let mut embeds = vec![embed];
+    async fn download_attachments(
+        attachments: Vec<Attachment>,
+    ) -> Result<Vec<AttachmentType<'static>>> {
+        let mut downloaded = Vec::with_capacity(attachments.len());
+        for attachment in attachments {
+            let bytes = attachment.download().await?;
+            downloaded.push(AttachmentType::Bytes {
+                data: Cow::from(bytes),
+                filename: attachment.filename,
+            })
+        }
+        Ok(downloaded)
+    }
 
-                        for embed in message.embeds {
-                            // copy embed data
-                            let mut builder = CreateEmbed::default();
-                            if let Some(title) = &embed.title {
-                                builder.title(title);
-                            }
-                            match embed.description {
-                                Some(description) => builder.description(description),
-                                None => builder.description("\u{200B}"),
-                            };
-                            if let Some(url) = &embed.url {
-                                builder.url(url);
-                            }
-                            if let Some(timestamp) = &embed.timestamp {
-                                builder.timestamp(timestamp.clone());
-                            }
-                            if let Some(image) = embed.image {
-                                builder.image(image.url);
-                            };
-                            if let Some(thumbnail) = embed.thumbnail {
-                                builder.thumbnail(thumbnail.url);
-                            };
-                            if let Some(color) = embed.colour {
-                                builder.color(color);
-                            };
-                            if let Some(footer) = embed.footer {
-                                builder.footer(|builder| {
-                                    builder.text(footer.text);
-                                    if let Some(icon_url) = &footer.icon_url {
-                                        builder.icon_url(icon_url);
-                                    }
-                                    builder
-                                });
-                            };
-                            if let Some(author) = embed.author {
-                                builder.author(|builder| {
-                                    builder.name(author.name);
-                                    if let Some(url) = &author.url {
-                                        builder.url(url);
-                                    }
-                                    if let Some(icon_url) = &author.icon_url {
-                                        builder.icon_url(icon_url);
-                                    }
-                                    builder
-                                });
-                            };
-                            for field in embed.fields {
-                                builder.field(field.name, field.value, field.inline);
-                            }
-                            embeds.push(builder)
-                        }
+    // finds or creates the webhook previews in a channel are sent through
+    async fn channel_webhook(&self, ctx: &BotContext, channel: ChannelId) -> Result<Webhook> {
+        if let Some(webhook) = self.webhooks.read().await.get(&channel) {
+            return Ok(webhook.clone());
+        }
 
-                        Ok((
-                            embeds,
-                            message
-                                .attachments
-                                .into_iter()
-                                .filter(|s| s.size < 8388246)
-                                .collect(),
-                        ))
-                    }
-                }
+        let bot = ctx.cache.current_user_id();
+        let existing = channel
+            .webhooks(&ctx.http)
+            .await?
+            .into_iter()
+            .find(|webhook| {
+                webhook.token.is_some() && webhook.user.as_ref().map(|u| u.id) == Some(bot)
+            });
+        let webhook = match existing {
+            Some(webhook) => webhook,
+            None => channel.create_webhook(&ctx.http, WEBHOOK_NAME).await?,
+        };
+
+        self.webhooks.write().await.insert(channel, webhook.clone());
+        Ok(webhook)
+    }
+
+    // webhook builders take raw json instead of embed builders
+    fn embed_value(embed: CreateEmbed) -> Value {
+        Value::Object(embed.0.into_iter().map(|(k, v)| (s!(k), v)).collect())
+    }
+
+    // content and embeds of a webhook preview, with a link back to the source at the end
+    fn webhook_body(
+        message: &Message,
+        guild: &Guild,
+        from_guild: &Option<GuildId>,
+    ) -> (String, Vec<Value>) {
+        let source = match from_guild {
+            Some(from_guild) if *from_guild == guild.id => s!(""),
+            _ => format!(" in {}", link_guild(guild, &message.channel_id)),
+        };
+        let mut link = CreateEmbed::default();
+        link.description(format!(
+            "[Jump to message]({}) from {}{}",
+            message.link(),
+            message.channel_id.mention(),
+            source
+        ));
+
+        // one embed slot goes to the link back
+        let embeds = message
+            .embeds
+            .iter()
+            .take(9)
+            .cloned()
+            .map(Self::copy_embed)
+            .chain(std::iter::once(link))
+            .map(Self::embed_value)
+            .collect::<Vec<Value>>();
+        let content = message
+            .content
+            .chars()
+            .take(MAX_CONTENT_LENGTH)
+            .collect::<String>();
+
+        (content, embeds)
+    }
+
+    // reposts a message through a webhook under the original author's name and avatar
+    async fn webhook_preview(
+        &self,
+        ctx: &BotContext,
+        target: ChannelId,
+        message: Message,
+        guild: &Guild,
+        from_guild: &Option<GuildId>,
+    ) -> Result<Vec<MessageId>> {
+        let webhook = self.channel_webhook(ctx, target).await?;
+
+        let (content, embeds) = Self::webhook_body(&message, guild, from_guild);
+        let files = Self::download_attachments(
+            message
+                .attachments
+                .into_iter()
+                .filter(|s| s.size < MAX_ATTACHMENT_SIZE)
+                .collect(),
+        )
+        .await?;
+        let username = message
+            .author
+            .name
+            .chars()
+            .take(MAX_WEBHOOK_NAME_LENGTH)
+            .collect::<String>();
+        let avatar = message.author.face();
+
+        let result = webhook
+            .execute(&ctx.http, true, |w| {
+                w.username(username)
+                    .avatar_url(avatar)
+                    .content(content)
+                    .embeds(embeds)
+                    .add_files(files)
+                    .components(Self::delete_button)
+                    // quoting a ping shouldn't ping again
+                    .allowed_mentions(|m| m.empty_parse())
+            })
+            .await;
+
+        match result {
+            Ok(sent) => Ok(sent.into_iter().map(|m| m.id).collect()),
+            Err(err) => {
+                // most likely deleted by someone, make a new one next time
+                self.webhooks.write().await.remove(&target);
+                Err(err.into())
             }
         }
     }
 
+    async fn send_preview(
+        ctx: &BotContext,
+        target: ChannelId,
+        embeds: Vec<CreateEmbed>,
+        attachments: Vec<Attachment>,
+    ) -> Result<Vec<MessageId>> {
+        let downloaded = Self::download_attachments(attachments).await?;
+        let chunks = embeds.chunks(10).collect::<Vec<_>>();
+        let mut sent = Vec::with_capacity(chunks.len() + 1);
+        for (index, chunk) in chunks.iter().enumerate() {
+            // the delete button goes on whichever message comes last
+            let last = downloaded.is_empty() && index + 1 == chunks.len();
+            let message = target
+                .send_message(&ctx.http, |m| {
+                    m.set_embeds(chunk.to_vec());
+                    if last {
+                        m.components(Self::delete_button);
+                    }
+                    m
+                })
+                .await?;
+            sent.push(message.id);
+        }
+        if !downloaded.is_empty() {
+            let message = target
+                .send_message(&ctx.http, |m| {
+                    m.files(downloaded).components(Self::delete_button)
+                })
+                .await?;
+            sent.push(message.id);
+        }
+        Ok(sent)
+    }
+
+    // remembers who a preview was made for, so they can delete it later
+    async fn record_preview(
+        &self,
+        pool: &PgPool,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        user_id: UserId,
+        messages: &[MessageId],
+    ) -> Result<()> {
+        let preview_id = match messages.first() {
+            Some(first) => *first,
+            None => return Ok(()),
+        };
+        for message in messages {
+            sqlx::query(
+                "insert into PreviewMessages (message_id, preview_id, guild_id, channel_id, user_id) values ($1, $2, $3, $4, $5)",
+            )
+            .bind(SqlId(*message))
+            .bind(SqlId(preview_id))
+            .bind(SqlId(guild_id))
+            .bind(SqlId(channel_id))
+            .bind(SqlId(user_id))
+            .execute(pool)
+            .await?;
+        }
+        self.preview_messages
+            .write()
+            .await
+            .extend(messages.iter().map(|message| (*message, guild_id)));
+        Ok(())
+    }
+
+    /// Forgets who old previews were made for, their delete buttons stop working after this
+    pub async fn prune_preview_messages(&self, pool: &PgPool) -> Result<()> {
+        // live previews still need their deletes, so those stay known
+        let expired = sqlx::query(
+            "with expired as (delete from PreviewMessages where created_at < $1 returning message_id) \
+             select message_id from expired where message_id not in (select message_id from LivePreviews)",
+        )
+        .bind(Utc::now() - Duration::days(PREVIEW_MESSAGE_DAYS))
+        .map(|row: PgRow| row.get::<SqlId<MessageId>, _>("message_id").0)
+        .fetch_all(pool)
+        .await?;
+
+        let mut handle = self.preview_messages.write().await;
+        for message in expired {
+            handle.remove(&message);
+        }
+        Ok(())
+    }
+
+    // forum posts are threads, so adding the forum covers every post in it
+    fn forum_of(ctx: &BotContext, guild_id: GuildId, channel: ChannelId) -> Option<ChannelId> {
+        let parent = ctx
+            .cache
+            .guild_field(guild_id, |g| {
+                g.threads
+                    .iter()
+                    .find(|thread| thread.id == channel)
+                    .and_then(|thread| thread.parent_id)
+            })
+            .flatten()?;
+        match ctx.cache.guild_channel(parent) {
+            Some(parent) if parent.kind == ChannelType::Forum => Some(parent.id),
+            _ => None,
+        }
+    }
+
     pub async fn message(&self, ctx: &BotContext, message: &Message) -> Result<()> {
         // ignore dms
         if message.guild_id.is_none() {
@@ -539,57 +1167,125 @@ impl PreviewsModule {
         }
 
         // detect if we should scan
+        let forum = Self::forum_of(ctx, message.guild_id.unwrap(), message.channel_id);
         let should_scan = self
             .read_cache(&message.guild_id.unwrap(), |cached| {
                 cached.auto_channels.contains(&message.channel_id)
+                    || forum.is_some_and(|forum| cached.auto_channels.contains(&forum))
             })
             .await;
         if !should_scan {
             return Ok(());
         }
 
-        for item in self.link_regex.captures_iter(&message.content) {
-            match self
-                .preview(
-                    ctx,
-                    &message.author.id,
-                    &message.guild_id,
-                    GuildId(
-                        u64::from_str(item.get(1).ok_or(BotError::Internal(0))?.as_str())
-                            .map_err(|_| BotError::Internal(1))?,
-                    ),
-                    ChannelId(
-                        u64::from_str(item.get(2).ok_or(BotError::Internal(2))?.as_str())
-                            .map_err(|_| BotError::Internal(3))?,
-                    ),
-                    MessageId(
-                        u64::from_str(item.get(3).ok_or(BotError::Internal(4))?.as_str())
-                            .map_err(|_| BotError::Internal(5))?,
-                    ),
+        let guild_id = message.guild_id.unwrap();
+        let (use_webhook, suppress_embeds, live) = self
+            .read_cache(&guild_id, |cached| {
+                (
+                    cached.webhook_channels.contains(&message.channel_id),
+                    cached.suppress_embeds,
+                    cached.live,
                 )
-                .await
-            {
-                Ok((embeds, attachments)) => {
-                    let chunks = embeds.chunks(10);
-                    let mut downloaded = Vec::with_capacity(attachments.len());
-                    for attachment in attachments {
-                        let bytes = attachment.download().await?;
-                        downloaded.push(AttachmentType::Bytes {
-                            data: Cow::from(bytes),
-                            filename: attachment.filename,
-                        })
-                    }
-                    for chunk in chunks {
-                        message
-                            .channel_id
-                            .send_message(&ctx.http, |m| m.set_embeds(chunk.to_vec()))
-                            .await?;
+            })
+            .await;
+        // webhooks can't post into threads, those get regular previews
+        let use_webhook = use_webhook && ctx.cache.guild_channel(message.channel_id).is_some();
+        let mut previewed = false;
+
+        for item in self.link_regex.captures_iter(&message.content) {
+            let source_channel = ChannelId(
+                u64::from_str(item.get(2).ok_or(BotError::Internal(2))?.as_str())
+                    .map_err(|_| BotError::Internal(3))?,
+            );
+            let source_message = MessageId(
+                u64::from_str(item.get(3).ok_or(BotError::Internal(4))?.as_str())
+                    .map_err(|_| BotError::Internal(5))?,
+            );
+            let fetched = Self::fetch_message(
+                ctx,
+                &message.author.id,
+                GuildId(
+                    u64::from_str(item.get(1).ok_or(BotError::Internal(0))?.as_str())
+                        .map_err(|_| BotError::Internal(1))?,
+                ),
+                source_channel,
+                source_message,
+            )
+            .await;
+
+            let edited = match &fetched {
+                Ok((source, _)) => source.edited_timestamp,
+                Err(_) => None,
+            };
+            let result = match fetched {
+                // system messages have no content of their own to repost, and neither do
+                // ones like polls that serenity can't read
+                Ok((source, guild))
+                    if use_webhook
+                        && matches!(
+                            source.kind,
+                            MessageType::Regular | MessageType::InlineReply
+                        )
+                        && !Self::needs_raw(&source) =>
+                {
+                    match self
+                        .webhook_preview(
+                            ctx,
+                            message.channel_id,
+                            source.clone(),
+                            &guild,
+                            &message.guild_id,
+                        )
+                        .await
+                    {
+                        Ok(sent) => Ok((sent, LiveKind::Webhook)),
+                        // usually missing manage webhooks, which shouldn't cost the preview
+                        Err(err) => {
+                            warn!(
+                                "webhook preview failed in {}, falling back to embeds: {:?}",
+                                message.channel_id, err
+                            );
+                            let (embeds, attachments) = self
+                                .build_preview(ctx, source, &guild, &message.guild_id)
+                                .await;
+                            Self::send_preview(ctx, message.channel_id, embeds, attachments)
+                                .await
+                                .map(|sent| (sent, LiveKind::Embed))
+                        }
                     }
-                    if !downloaded.is_empty() {
-                        message
-                            .channel_id
-                            .send_message(&ctx.http, |m| m.files(downloaded))
-                            .await?;
+                }
+                Ok((source, guild)) => {
+                    let (embeds, attachments) = self
+                        .build_preview(ctx, source, &guild, &message.guild_id)
+                        .await;
+                    Self::send_preview(ctx, message.channel_id, embeds, attachments)
+                        .await
+                        .map(|sent| (sent, LiveKind::Embed))
+                }
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok((sent, kind)) => {
+                    previewed = true;
+                    self.record_preview(
+                        &ctx.pool,
+                        guild_id,
+                        message.channel_id,
+                        message.author.id,
+                        &sent,
+                    )
+                    .await?;
+                    // the source is rendered into the first message, the rest is overflow
+                    if let (true, Some(first)) = (live, sent.first()) {
+                        self.record_live(
+                            &ctx.pool,
+                            guild_id,
+                            (message.channel_id, *first),
+                            (source_channel, source_message, edited),
+                            kind,
+                        )
+                        .await?;
                     }
                 }
                 Err(err) => {
@@ -600,24 +1296,393 @@ impl PreviewsModule {
             }
         }
 
+        if previewed && suppress_embeds {
+            // needs manage messages, which not every server gives out
+            if let Err(err) = message.clone().suppress_embeds(ctx).await {
+                debug!("failed to suppress embeds on {}: {:?}", message.id, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_component(
+        &self,
+        ctx: &BotContext,
+        interaction: &MessageComponentInteraction,
+        manager: bool,
+    ) -> Result<()> {
+        defer_component(&ctx, interaction).await?;
+
+        let (preview_id, user_id) = match sqlx::query(
+            "select preview_id, user_id from PreviewMessages where message_id = $1",
+        )
+        .bind(SqlId(interaction.message.id))
+        .map(|row: PgRow| {
+            (
+                row.get::<SqlId<MessageId>, _>("preview_id").0,
+                row.get::<SqlId<UserId>, _>("user_id").0,
+            )
+        })
+        .fetch_optional(&ctx.pool)
+        .await?
+        {
+            Some(found) => found,
+            None => {
+                return FollowupBuilder::new()
+                    .description(format!(
+                        "Previews can only be deleted this way for {} days",
+                        PREVIEW_MESSAGE_DAYS
+                    ))
+                    .ephemeral()
+                    .build_component_followup(&ctx, interaction)
+                    .await;
+            }
+        };
+
+        if user_id != interaction.user.id && !manager {
+            return FollowupBuilder::new()
+                .description(format!(
+                    "Only {} or someone with `Manage Previews` can delete this preview",
+                    user_id.mention()
+                ))
+                .ephemeral()
+                .build_component_followup(&ctx, interaction)
+                .await;
+        }
+
+        let messages = sqlx::query("select message_id from PreviewMessages where preview_id = $1")
+            .bind(SqlId(preview_id))
+            .map(|row: PgRow| row.get::<SqlId<MessageId>, _>("message_id").0)
+            .fetch_all(&ctx.pool)
+            .await?;
+
+        // webhook messages can be deleted through the webhook, no manage messages needed
+        let webhook = match interaction.message.webhook_id {
+            Some(_) => Some(self.channel_webhook(ctx, interaction.channel_id).await?),
+            None => None,
+        };
+        for message in messages {
+            let mut result = match &webhook {
+                Some(webhook) => webhook.delete_message(&ctx.http, message).await,
+                None => {
+                    interaction
+                        .channel_id
+                        .delete_message(&ctx.http, message)
+                        .await
+                }
+            };
+            // the webhook may have been recreated since, so the old messages aren't its own anymore
+            if webhook.is_some() && result.is_err() && !Self::unknown_message(&result) {
+                result = interaction
+                    .channel_id
+                    .delete_message(&ctx.http, message)
+                    .await;
+            }
+            // some of it may have been deleted by hand already
+            if result.is_err() && !Self::unknown_message(&result) {
+                debug!("failed to delete preview message {}: {:?}", message, result);
+                continue;
+            }
+
+            sqlx::query("delete from PreviewMessages where message_id = $1")
+                .bind(SqlId(message))
+                .execute(&ctx.pool)
+                .await?;
+            self.preview_messages.write().await.remove(&message);
+        }
+
+        Ok(())
+    }
+
+    fn unknown_message<T>(result: &serenity::Result<T>) -> bool {
+        matches!(
+            result,
+            Err(SerenityError::Http(e)) if matches!(&**e, HttpError::UnsuccessfulRequest(r) if r.error.code == 10008)
+        )
+    }
+
+    async fn record_live(
+        &self,
+        pool: &PgPool,
+        guild_id: GuildId,
+        (channel_id, message_id): (ChannelId, MessageId),
+        (source_channel, source_message, edited): (ChannelId, MessageId, Option<Timestamp>),
+        kind: LiveKind,
+    ) -> Result<()> {
+        sqlx::query(
+            "insert into LivePreviews (message_id, guild_id, channel_id, source_channel_id, source_message_id, kind) \
+             values ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(SqlId(message_id))
+        .bind(SqlId(guild_id))
+        .bind(SqlId(channel_id))
+        .bind(SqlId(source_channel))
+        .bind(SqlId(source_message))
+        .bind(kind.as_value())
+        .execute(pool)
+        .await?;
+        self.live_sources
+            .write()
+            .await
+            .insert(source_message, edited);
+        self.preview_messages
+            .write()
+            .await
+            .insert(message_id, guild_id);
+        Ok(())
+    }
+
+    async fn live_previews(pool: &PgPool, source: MessageId) -> Result<Vec<LivePreview>> {
+        sqlx::query(
+            "select message_id, guild_id, channel_id, kind from LivePreviews where source_message_id = $1",
+        )
+        .bind(SqlId(source))
+        .map(|row: PgRow| -> Result<LivePreview> {
+            Ok(LivePreview {
+                message_id: row.get::<SqlId<MessageId>, _>("message_id").0,
+                guild_id: row.get::<SqlId<GuildId>, _>("guild_id").0,
+                channel_id: row.get::<SqlId<ChannelId>, _>("channel_id").0,
+                kind: LiveKind::from_value(row.get("kind"))?,
+            })
+        })
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect()
+    }
+
+    pub async fn message_update(&self, ctx: &BotContext, event: &MessageUpdateEvent) -> Result<()> {
+        // unfurls, pins and embed suppression arrive as updates too, only act on actual edits
+        match (
+            self.live_sources.write().await.get_mut(&event.id),
+            event.edited_timestamp,
+        ) {
+            (Some(last), Some(edited)) if *last != Some(edited) => *last = Some(edited),
+            _ => return Ok(()),
+        }
+
+        let guild = match event
+            .guild_id
+            .and_then(|guild_id| ctx.cache.guild(guild_id))
+        {
+            Some(guild) => guild,
+            None => return Ok(()),
+        };
+        let mut source = event.channel_id.message(&ctx.http, event.id).await?;
+        source.guild_id = Some(guild.id);
+
+        for preview in Self::live_previews(&ctx.pool, event.id).await? {
+            let from_guild = Some(preview.guild_id);
+            let result = match preview.kind {
+                LiveKind::Embed => {
+                    let (embeds, _) = self
+                        .build_preview(ctx, source.clone(), &guild, &from_guild)
+                        .await;
+                    preview
+                        .channel_id
+                        .edit_message(&ctx.http, preview.message_id, |m| {
+                            m.set_embeds(embeds.into_iter().take(10).collect())
+                        })
+                        .await
+                        .map_err(Error::from)
+                }
+                LiveKind::Webhook => {
+                    let (content, embeds) = Self::webhook_body(&source, &guild, &from_guild);
+                    match self.channel_webhook(ctx, preview.channel_id).await {
+                        Ok(webhook) => webhook
+                            .edit_message(&ctx.http, preview.message_id, |m| {
+                                m.content(content).embeds(embeds)
+                            })
+                            .await
+                            .map_err(Error::from),
+                        Err(err) => Err(err),
+                    }
+                }
+                // archives are a record, so the original stays and the edit is added after it
+                LiveKind::Archive => {
+                    let (embeds, _) = self
+                        .build_preview(ctx, source.clone(), &guild, &from_guild)
+                        .await;
+                    let mut embeds = embeds.into_iter().take(10).collect::<Vec<_>>();
+                    embeds[0].title("Edited revision");
+                    preview
+                        .channel_id
+                        .send_message(&ctx.http, |m| {
+                            m.reference_message((preview.channel_id, preview.message_id))
+                                .allowed_mentions(|m| m.empty_parse())
+                                .set_embeds(embeds)
+                        })
+                        .await
+                        .map_err(Error::from)
+                }
+            };
+            // the preview itself may be gone, which isn't worth failing the rest over
+            if let Err(err) = result {
+                debug!(
+                    "failed to update live preview {}: {:?}",
+                    preview.message_id, err
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn message_delete(
+        &self,
+        ctx: &BotContext,
+        channel_id: &ChannelId,
+        message_id: &MessageId,
+    ) -> Result<()> {
+        let preview = self
+            .preview_messages
+            .write()
+            .await
+            .remove(message_id)
+            .is_some();
+        let source = self.live_sources.write().await.remove(message_id).is_some();
+
+        // a deleted preview has nothing left to keep in sync or delete
+        if preview {
+            sqlx::query("delete from LivePreviews where message_id = $1")
+                .bind(SqlId(*message_id))
+                .execute(&ctx.pool)
+                .await?;
+            sqlx::query("delete from PreviewMessages where message_id = $1")
+                .bind(SqlId(*message_id))
+                .execute(&ctx.pool)
+                .await?;
+        }
+
+        if !source {
+            return Ok(());
+        }
+
+        let previews = Self::live_previews(&ctx.pool, *message_id).await?;
+        sqlx::query("delete from LivePreviews where source_message_id = $1")
+            .bind(SqlId(*message_id))
+            .execute(&ctx.pool)
+            .await?;
+
+        for preview in previews {
+            let result = match preview.kind {
+                LiveKind::Embed | LiveKind::Webhook => self.mark_deleted(ctx, &preview).await,
+                LiveKind::Archive => preview
+                    .channel_id
+                    .send_message(&ctx.http, |m| {
+                        m.reference_message((preview.channel_id, preview.message_id))
+                            .embed(|e| {
+                                e.description(format!(
+                                    "{} in {}",
+                                    SOURCE_DELETED,
+                                    channel_id.mention()
+                                ))
+                            })
+                    })
+                    .await
+                    .map(|_| ())
+                    .map_err(Error::from),
+            };
+            if let Err(err) = result {
+                debug!(
+                    "failed to mark live preview {}: {:?}",
+                    preview.message_id, err
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    // flags the rendered source in a preview as deleted, leaving its content in place
+    async fn mark_deleted(&self, ctx: &BotContext, preview: &LivePreview) -> Result<()> {
+        let message = preview
+            .channel_id
+            .message(&ctx.http, preview.message_id)
+            .await?;
+        let mut embeds = message
+            .embeds
+            .into_iter()
+            .map(CreateEmbed::from)
+            .collect::<Vec<_>>();
+
+        match preview.kind {
+            LiveKind::Webhook => {
+                // the link back is the last embed
+                if let Some(link) = embeds.last_mut() {
+                    link.footer(|f| f.text(SOURCE_DELETED));
+                }
+                let embeds = embeds.into_iter().map(Self::embed_value).collect();
+                self.channel_webhook(ctx, preview.channel_id)
+                    .await?
+                    .edit_message(&ctx.http, preview.message_id, |m| m.embeds(embeds))
+                    .await?;
+            }
+            LiveKind::Embed | LiveKind::Archive => {
+                if let Some(first) = embeds.first_mut() {
+                    first.footer(|f| f.text(SOURCE_DELETED));
+                }
+                preview
+                    .channel_id
+                    .edit_message(&ctx.http, preview.message_id, |m| m.set_embeds(embeds))
+                    .await?;
+            }
+        }
         Ok(())
     }
 
     pub async fn guild_data(&self, ctx: &BotContext, guild: &Guild) -> Result<()> {
+        let unknown = self
+            .read_cache(&guild.id, |cached| {
+                cached
+                    .auto_channels
+                    .iter()
+                    .filter(|channel| {
+                        !guild.channels.contains_key(channel)
+                            && !guild.threads.iter().any(|thread| thread.id == **channel)
+                    })
+                    .copied()
+                    .collect::<Vec<ChannelId>>()
+            })
+            .await;
+
+        // archived threads aren't part of the guild, only forget what discord says is gone
+        let mut gone = Vec::new();
+        for channel in unknown {
+            match channel.to_channel(ctx).await {
+                Ok(Channel::Guild(found)) if found.guild_id == guild.id => {}
+                Ok(_) => gone.push(channel),
+                // "Unknown Channel"
+                Err(SerenityError::Http(e)) if matches!(&*e, HttpError::UnsuccessfulRequest(r) if r.error.code == 10003) => {
+                    gone.push(channel)
+                }
+                Err(e) => warn!("couldn't look up preview channel {}: {:?}", channel, e),
+            }
+        }
+        if gone.is_empty() {
+            return Ok(());
+        }
+
         // remove invalid channels
         let entries = self
             .write_cache(&guild.id, |cached| {
-                let mut indexes = Vec::new();
-                for (index, channel) in cached.auto_channels.iter().enumerate() {
-                    if !guild.channels.contains_key(channel) {
-                        indexes.push(index);
-                    }
-                }
+                let indexes = cached
+                    .auto_channels
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, channel)| gone.contains(channel))
+                    .map(|(index, _)| index)
+                    .collect::<Vec<usize>>();
+                cached
+                    .webhook_channels
+                    .retain(|channel| !gone.contains(channel));
                 remove_indexes(&mut cached.auto_channels, &indexes)
             })
             .await;
 
         for entry in entries {
+            self.webhooks.write().await.remove(&entry);
             sqlx::query("delete from PreviewChannels where guild_id = $1 and channel_id = $2")
                 .bind(guild.id.0 as i64)
                 .bind(entry.0 as i64)
@@ -637,8 +1702,10 @@ impl PreviewsModule {
         if let Some(s) = remove {
             self.write_cache(&channel.guild_id, |cached| {
                 cached.auto_channels.remove(s);
+                cached.webhook_channels.retain(|c| *c != channel.id);
             })
             .await;
+            self.webhooks.write().await.remove(&channel.id);
             sqlx::query("delete from PreviewChannels where guild_id = $1 and channel_id = $2")
                 .bind(SqlId(channel.guild_id))
                 .bind(SqlId(channel.id))
@@ -656,30 +1723,55 @@ impl PreviewsModule {
     ) -> Result<()> {
         defer_command(&ctx, interaction).await?;
         let target = args.get_channel("target")?;
+        let webhook = args.get_boolean("webhook").unwrap_or(false);
         let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
 
-        let already_added = self
+        if webhook && !matches!(target.kind, ChannelType::Text | ChannelType::News) {
+            return Err(Error::new(BotError::Generic(s!(
+                "Webhook previews are only supported in text and announcement channels"
+            ))));
+        }
+
+        // None if the channel is already added in the same mode
+        let before = self
             .write_cache(&guild_id, |data| {
-                if data.auto_channels.contains(&target.id) {
-                    true
-                } else {
+                let added = data.auto_channels.contains(&target.id);
+                let was_webhook = data.webhook_channels.contains(&target.id);
+                if added && was_webhook == webhook {
+                    return None;
+                }
+                if !added {
                     data.auto_channels.push(target.id);
-                    false
                 }
+                if webhook {
+                    data.webhook_channels.push(target.id);
+                } else {
+                    data.webhook_channels.retain(|c| *c != target.id);
+                }
+                Some(added.then_some(was_webhook))
             })
-            .await;
-        if already_added {
-            return Err(Error::new(BotError::Generic(
-                "Channel already added".to_string(),
-            )));
-        }
+            .await
+            .ok_or_else(|| BotError::Generic(s!("Channel already added")))?;
 
-        sqlx::query("insert into PreviewChannels (guild_id, channel_id) values ($1, $2) on conflict do nothing")
+        sqlx::query("insert into PreviewChannels (guild_id, channel_id, webhook) values ($1, $2, $3) on conflict (channel_id) do update set webhook = $3")
             .bind(SqlId(guild_id))
             .bind(SqlId(target.id))
+            .bind(webhook)
             .execute(&ctx.pool)
             .await?;
 
+        let mode = |webhook: bool| s!(if webhook { "Webhook" } else { "Embed" });
+        AuditEntry {
+            guild_id,
+            actor: interaction.user.id,
+            action: AuditAction::PreviewChannelAdd,
+            target: target.id.mention().to_string(),
+            before: before.map(mode),
+            after: Some(mode(webhook)),
+        }
+        .record(&ctx.pool)
+        .await?;
+
         FollowupBuilder::new()
             .description("Success")
             .build_command_followup(&ctx, interaction)
@@ -701,6 +1793,7 @@ impl PreviewsModule {
                 match data.auto_channels.binary_search(&target.id) {
                     Ok(s) => {
                         data.auto_channels.remove(s);
+                        data.webhook_channels.retain(|c| *c != target.id);
                         false
                     }
                     Err(_) => true,
@@ -718,6 +1811,18 @@ impl PreviewsModule {
             .bind(SqlId(target.id))
             .execute(&ctx.pool)
             .await?;
+        self.webhooks.write().await.remove(&target.id);
+
+        AuditEntry {
+            guild_id,
+            actor: interaction.user.id,
+            action: AuditAction::PreviewChannelRemove,
+            target: target.id.mention().to_string(),
+            before: None,
+            after: None,
+        }
+        .record(&ctx.pool)
+        .await?;
 
         FollowupBuilder::new()
             .description("Success")
@@ -735,7 +1840,11 @@ impl PreviewsModule {
 
         self.read_cache(&interaction.guild_id.ok_or(BotError::GuildOnly)?, |data| {
             for channel in &data.auto_channels {
-                items.push(channel.mention().to_string())
+                if data.webhook_channels.contains(channel) {
+                    items.push(format!("{} (webhook)", channel.mention()))
+                } else {
+                    items.push(channel.mention().to_string())
+                }
             }
         })
         .await;
@@ -787,16 +1896,8 @@ impl PreviewsModule {
             )
             .await?;
 
-        let chunks = embeds.chunks(10);
-        let mut downloaded = Vec::with_capacity(attachments.len());
-        for attachment in attachments {
-            let bytes = attachment.download().await?;
-            downloaded.push(AttachmentType::Bytes {
-                data: Cow::from(bytes),
-                filename: attachment.filename,
-            })
-        }
-        for chunk in chunks {
+        let downloaded = Self::download_attachments(attachments).await?;
+        for chunk in embeds.chunks(10) {
             interaction
                 .create_followup_message(&ctx.http, |m| m.set_embeds(chunk.to_vec()))
                 .await?;
@@ -815,7 +1916,12 @@ impl PreviewsModule {
         pool: &PgPool,
         from: Option<ChannelId>,
         guild_id: GuildId,
+        actor: &UserId,
     ) -> Result<()> {
+        let before = self
+            .read_cache(&guild_id, |data| data.archive_channel)
+            .await;
+
         match from {
             // set
             Some(target) => {
@@ -825,8 +1931,8 @@ impl PreviewsModule {
                 .await;
 
                 sqlx::query("insert into ArchiveChannel (guild_id, channel_id) values ($1, $2) on conflict on constraint archive_idx do update set channel_id = $2")
-                    .bind(&SqlId(guild_id))
-                    .bind(&SqlId(target))
+                    .bind(SqlId(guild_id))
+                    .bind(SqlId(target))
                     .execute(pool)
                     .await?;
             }
@@ -838,14 +1944,215 @@ impl PreviewsModule {
                 .await;
 
                 sqlx::query("delete from ArchiveChannel where guild_id = $1")
-                    .bind(&SqlId(guild_id))
+                    .bind(SqlId(guild_id))
                     .execute(pool)
                     .await?;
             }
         };
+
+        if before != from {
+            AuditEntry {
+                guild_id,
+                actor: *actor,
+                action: AuditAction::ArchiveChannel,
+                target: s!("Archive channel"),
+                before: before.map(|c| c.mention().to_string()),
+                after: from.map(|c| c.mention().to_string()),
+            }
+            .record(pool)
+            .await?;
+        }
         Ok(())
     }
 
+    /// Updates the setting, returning the previous value
+    pub async fn set_suppress_embeds(
+        &self,
+        pool: &PgPool,
+        guild_id: &GuildId,
+        value: bool,
+    ) -> Result<bool> {
+        let before = self
+            .write_cache(guild_id, |data| {
+                std::mem::replace(&mut data.suppress_embeds, value)
+            })
+            .await;
+
+        sqlx::query(
+            "insert into PreviewSettings (guild_id, suppress_embeds) values ($1, $2) \
+             on conflict on constraint preview_settings_idx do update set suppress_embeds = $2",
+        )
+        .bind(SqlId(*guild_id))
+        .bind(value)
+        .execute(pool)
+        .await?;
+
+        Ok(before)
+    }
+
+    pub async fn previews_suppress_embeds(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let value = args.get_boolean("enabled")?;
+        let before = self
+            .set_suppress_embeds(&ctx.pool, &guild_id, value)
+            .await?;
+
+        if before != value {
+            AuditEntry {
+                guild_id,
+                actor: interaction.user.id,
+                action: AuditAction::PreviewSetting,
+                target: s!("Suppress link embeds"),
+                before: Some(before.to_string()),
+                after: Some(value.to_string()),
+            }
+            .record(&ctx.pool)
+            .await?;
+        }
+
+        FollowupBuilder::new()
+            .description(if value {
+                "Link embeds are now hidden on previewed messages"
+            } else {
+                "Link embeds are now left alone on previewed messages"
+            })
+            .build_command_followup(&ctx.http, interaction)
+            .await
+    }
+
+    /// Updates the setting, returning the previous value
+    pub async fn set_live(&self, pool: &PgPool, guild_id: &GuildId, value: bool) -> Result<bool> {
+        let before = self
+            .write_cache(guild_id, |data| std::mem::replace(&mut data.live, value))
+            .await;
+
+        sqlx::query(
+            "insert into PreviewSettings (guild_id, live) values ($1, $2) \
+             on conflict on constraint preview_settings_idx do update set live = $2",
+        )
+        .bind(SqlId(*guild_id))
+        .bind(value)
+        .execute(pool)
+        .await?;
+
+        Ok(before)
+    }
+
+    pub async fn previews_live(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let value = args.get_boolean("enabled")?;
+        let before = self.set_live(&ctx.pool, &guild_id, value).await?;
+
+        if before != value {
+            AuditEntry {
+                guild_id,
+                actor: interaction.user.id,
+                action: AuditAction::PreviewSetting,
+                target: s!("Live previews"),
+                before: Some(before.to_string()),
+                after: Some(value.to_string()),
+            }
+            .record(&ctx.pool)
+            .await?;
+        }
+
+        FollowupBuilder::new()
+            .description(if value {
+                "New previews and archive entries now follow edits and deletions of their source"
+            } else {
+                "New previews and archive entries are now snapshots"
+            })
+            .build_command_followup(&ctx.http, interaction)
+            .await
+    }
+
+    pub async fn export_config(&self, guild_id: &GuildId) -> PreviewSection {
+        self.read_cache(guild_id, |data| PreviewSection {
+            channels: data.auto_channels.clone(),
+            webhook_channels: data.webhook_channels.clone(),
+            archive: data.archive_channel,
+            suppress_embeds: data.suppress_embeds,
+            live: data.live,
+        })
+        .await
+    }
+
+    pub async fn import_config(
+        &self,
+        conn: &mut PgConnection,
+        guild_id: &GuildId,
+        config: &PreviewSection,
+    ) -> Result<()> {
+        sqlx::query("delete from PreviewChannels where guild_id = $1")
+            .bind(SqlId(*guild_id))
+            .execute(&mut *conn)
+            .await?;
+        for channel in &config.channels {
+            sqlx::query("insert into PreviewChannels (guild_id, channel_id, webhook) values ($1, $2, $3) on conflict do nothing")
+                .bind(SqlId(*guild_id))
+                .bind(SqlId(*channel))
+                .bind(config.webhook_channels.contains(channel))
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        sqlx::query(
+            "insert into PreviewSettings (guild_id, suppress_embeds, live) values ($1, $2, $3) \
+             on conflict on constraint preview_settings_idx do update set suppress_embeds = $2, live = $3",
+        )
+        .bind(SqlId(*guild_id))
+        .bind(config.suppress_embeds)
+        .bind(config.live)
+        .execute(&mut *conn)
+        .await?;
+
+        match config.archive {
+            Some(target) => {
+                sqlx::query("insert into ArchiveChannel (guild_id, channel_id) values ($1, $2) on conflict on constraint archive_idx do update set channel_id = $2")
+                    .bind(SqlId(*guild_id))
+                    .bind(SqlId(target))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            None => {
+                sqlx::query("delete from ArchiveChannel where guild_id = $1")
+                    .bind(SqlId(*guild_id))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts an imported configuration in the cache, once it's been saved
+    pub async fn cache_config(&self, guild_id: &GuildId, config: &PreviewSection) {
+        self.write_cache(guild_id, |data| {
+            data.auto_channels = config.channels.clone();
+            data.webhook_channels = config
+                .webhook_channels
+                .iter()
+                .filter(|c| config.channels.contains(c))
+                .copied()
+                .collect();
+            data.archive_channel = config.archive;
+            data.suppress_embeds = config.suppress_embeds;
+            data.live = config.live;
+        })
+        .await
+    }
+
     pub async fn previews_archive(
         &self,
         ctx: &BotContext,
@@ -858,6 +2165,7 @@ impl PreviewsModule {
             &ctx.pool,
             args.get_channel("target").ok().map(|c| c.id),
             guild_id,
+            &interaction.user.id,
         )
         .await?;
 
@@ -913,19 +2221,13 @@ impl PreviewsModule {
         embeds.push(embed);
         embeds.extend(iter);
 
-        let chunks = embeds.chunks(10);
-        let mut downloaded = Vec::with_capacity(attachments.len());
-        for attachment in attachments {
-            let bytes = attachment.download().await?;
-            downloaded.push(AttachmentType::Bytes {
-                data: Cow::from(bytes),
-                filename: attachment.filename,
-            })
-        }
-        for chunk in chunks {
-            archive_channel
+        let downloaded = Self::download_attachments(attachments).await?;
+        let mut first = None;
+        for chunk in embeds.chunks(10) {
+            let sent = archive_channel
                 .send_message(ctx, |m| m.set_embeds(chunk.to_vec()))
                 .await?;
+            first.get_or_insert(sent.id);
         }
         if !downloaded.is_empty() {
             archive_channel
@@ -933,6 +2235,18 @@ impl PreviewsModule {
                 .await?;
         }
 
+        let live = self.read_cache(&guild_id, |data| data.live).await;
+        if let (true, Some(first)) = (live, first) {
+            self.record_live(
+                &ctx.pool,
+                guild_id,
+                (archive_channel, first),
+                (interaction.channel_id, message.id, message.edited_timestamp),
+                LiveKind::Archive,
+            )
+            .await?;
+        }
+
         FollowupBuilder::new()
             .description("Success")
             .build_command_edit(ctx, interaction)
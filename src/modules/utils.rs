@@ -3,124 +3,706 @@
 // You should have received a copy of the license along with this program
 // If not, see <https://www.gnu.org/licenses/#AGPL>
 
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
 use crate::decode::SlashMap;
+use crate::impl_cache_functions;
+use crate::modules::audit::{AuditAction, AuditEntry};
+use crate::modules::cases::{CaseAction, DmStatus, NewCase};
+use crate::modules::config::{ModerationSection, PresetSection};
 use crate::prelude::*;
-use crate::utils::{highest_role, link_guild, parse_duration, FollowupBuilder};
-use anyhow::Result;
+use crate::tasks::{cancel_job, expedite_job, pending_jobs, schedule_job, Job, TaskMessage};
+use crate::utils::{
+    default_arg, defer_command, describe_duration, display_duration, highest_role, link_guild,
+    parse_duration, FollowupBuilder, SqlId,
+};
+use anyhow::{Error, Result};
+use chrono::Utc;
+use regex::Regex;
 use serenity::builder::CreateEmbed;
+use serenity::http::HttpError;
+use serenity::model::channel::GuildChannel;
+use serenity::model::guild::{Member, Role};
+use serenity::model::id::{ChannelId, GuildId, UserId};
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::interaction::autocomplete::AutocompleteInteraction;
+use serenity::model::user::User;
 use serenity::model::Timestamp;
-use serenity::prelude::Mentionable;
+use serenity::prelude::{Mentionable, SerenityError};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Row};
+use std::collections::HashMap;
+use tokio::sync::{broadcast, RwLock};
 
-pub struct UtilsModule {}
+/// Actions that go through the shared moderation pipeline
+#[derive(Copy, Clone)]
+pub enum Sanction {
+    Timeout,
+    Kick,
+    Ban { delete_message_days: u8 },
+    Softban { delete_message_days: u8 },
+}
+
+impl Sanction {
+    fn case_action(&self) -> CaseAction {
+        match self {
+            Sanction::Timeout => CaseAction::Timeout,
+            Sanction::Kick => CaseAction::Kick,
+            Sanction::Ban { .. } => CaseAction::Ban,
+            Sanction::Softban { .. } => CaseAction::Softban,
+        }
+    }
+
+    // used in "Can't {} owner"
+    fn infinitive(&self) -> &'static str {
+        match self {
+            Sanction::Timeout => "time out",
+            Sanction::Kick => "kick",
+            Sanction::Ban { .. } => "ban",
+            Sanction::Softban { .. } => "softban",
+        }
+    }
+
+    // used in "{} was {}"
+    fn past_tense(&self) -> &'static str {
+        match self {
+            Sanction::Timeout => "muted",
+            Sanction::Kick => "kicked",
+            Sanction::Ban { .. } => "banned",
+            Sanction::Softban { .. } => "softbanned",
+        }
+    }
+
+    // everything but bans needs the target to actually be in the server
+    fn needs_member(&self) -> bool {
+        !matches!(self, Sanction::Ban { .. })
+    }
+
+    // once someone is removed from the server we can't message them anymore
+    fn dm_first(&self) -> bool {
+        !matches!(self, Sanction::Timeout)
+    }
+}
+
+pub struct ReasonPreset {
+    pub name: String,
+    pub reason: String,
+}
+
+pub struct ModerationSettings {
+    pub shame: bool,
+    pub dm: bool,
+    pub anon: bool,
+    pub max_timeout: Option<Duration>,
+    pub require_reason: bool,
+    pub presets: Vec<ReasonPreset>,
+    pub log_channel: Option<ChannelId>,
+}
+
+impl Default for ModerationSettings {
+    fn default() -> Self {
+        Self {
+            shame: true,
+            dm: true,
+            anon: false,
+            max_timeout: None,
+            require_reason: false,
+            presets: Vec::new(),
+            log_channel: None,
+        }
+    }
+}
+
+/// What came out of a carried out sanction
+pub struct Outcome {
+    pub case_id: i32,
+    // formatted end time, if there is one
+    pub until: Option<String>,
+    // none when no dm was attempted
+    pub dm_status: Option<DmStatus>,
+}
+
+pub struct Enforcement {
+    pub sanction: Sanction,
+    pub target: User,
+    pub member: Option<Member>,
+    pub reason: String,
+    pub duration: Option<Duration>,
+    // handed out for the length of a timeout, then removed by the job worker
+    pub role: Option<Role>,
+    pub shame: bool,
+    pub dm: bool,
+    pub anon: bool,
+}
+
+// discord caps timeouts at 28 days
+const MAX_TIMEOUT: Duration = Duration::from_secs(28 * 60 * 60 * 24);
+// temporary bans longer than this should just be permanent
+const MAX_BAN: Duration = Duration::from_secs(365 * 60 * 60 * 24);
+// reminders further out than this are probably a typo
+const MAX_REMINDER: Duration = Duration::from_secs(365 * 60 * 60 * 24);
+// pending reminders per user and server
+const MAX_REMINDERS: usize = 10;
+// keeps the result embed within discord's limits
+const MAX_BULK_TARGETS: usize = 25;
+// discord allows at most 25 autocomplete choices, each up to 100 characters
+const MAX_CHOICES: usize = 25;
+const MAX_CHOICE_LENGTH: usize = 100;
+const MAX_PRESETS: usize = 25;
+const MAX_PRESET_NAME_LENGTH: usize = 32;
+const DURATION_SUGGESTIONS: [&str; 6] = ["10m", "1h", "6h", "1d", "1w", "28d"];
+const UNIT_SUGGESTIONS: [&str; 4] = ["m", "h", "d", "w"];
+const DEFAULT_REASONS: [&str; 5] = [
+    "Spam",
+    "Harassment",
+    "Inappropriate content",
+    "Off-topic after warning",
+    "Raid",
+];
+
+// autocomplete choice names longer than discord allows get cut off
+fn truncate_choice(from: String) -> String {
+    if from.chars().count() <= MAX_CHOICE_LENGTH {
+        from
+    } else {
+        let mut truncated = from.chars().take(MAX_CHOICE_LENGTH - 1).collect::<String>();
+        truncated.push('…');
+        truncated
+    }
+}
+
+pub struct UtilsModule {
+    user_regex: Regex,
+    settings: RwLock<HashMap<GuildId, ModerationSettings>>,
+}
 
 impl UtilsModule {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            user_regex: Regex::new(r"<@!?(\d+)>|(\d{15,20})")?,
+            settings: Default::default(),
+        })
     }
 
-    pub async fn timeout_command(
-        &self,
-        ctx: &BotContext,
-        interaction: &ApplicationCommandInteraction,
-        args: SlashMap,
+    impl_cache_functions!(
+        read_settings,
+        write_settings,
+        write_settings_async,
+        GuildId,
+        ModerationSettings,
+        settings,
+        default_arg
+    );
+
+    pub async fn initialize(
+        instance: Arc<Self>,
+        mut task_rx: broadcast::Receiver<TaskMessage>,
+        pool: &PgPool,
     ) -> Result<()> {
-        let reason = args.get_string("reason")?;
-        let shame = args.get_boolean("shame").unwrap_or(true);
-        let dm = args.get_boolean("dm").unwrap_or(true);
-        let anon = args.get_boolean("anon").unwrap_or(false);
-        let duration = match parse_duration(&args.get_string("duration")?) {
-            Some(s) => s,
-            None => {
-                return FollowupBuilder::new()
-                    .description("Duration is malformed")
-                    .set_ephemeral(anon)
-                    .build_command_response(&ctx, interaction)
-                    .await;
-            }
-        };
-        let until = Timestamp::from_unix_timestamp(
-            (SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + duration.as_secs()) as i64,
-        )?;
+        let rows = sqlx::query(
+            "select guild_id, default_shame, default_dm, default_anon, max_timeout, require_reason, log_channel from ModerationSettings",
+        )
+        .fetch_all(pool)
+        .await?;
 
-        let target = interaction
-            .guild_id
-            .unwrap()
-            .member(&ctx, args.get_user("target")?.id())
+        for row in rows {
+            instance
+                .write_settings(&row.get::<SqlId<GuildId>, _>("guild_id").0, |settings| {
+                    settings.shame = row.get("default_shame");
+                    settings.dm = row.get("default_dm");
+                    settings.anon = row.get("default_anon");
+                    settings.max_timeout = row
+                        .get::<Option<i64>, _>("max_timeout")
+                        .map(|d| Duration::from_secs(d as u64));
+                    settings.require_reason = row.get("require_reason");
+                    settings.log_channel = row
+                        .get::<Option<SqlId<ChannelId>>, _>("log_channel")
+                        .map(|c| c.0);
+                })
+                .await;
+        }
+
+        let rows = sqlx::query("select guild_id, name, reason from ReasonPresets order by name")
+            .fetch_all(pool)
             .await?;
 
-        if shame && !anon {
-            interaction.defer(ctx).await?;
+        for row in rows {
+            instance
+                .write_settings(&row.get::<SqlId<GuildId>, _>("guild_id").0, |settings| {
+                    settings.presets.push(ReasonPreset {
+                        name: row.get("name"),
+                        reason: row.get("reason"),
+                    });
+                })
+                .await;
         }
 
-        // TODO: break out rest of this functionality into a generic function that can be used by other modules
+        // task event handling
+        tokio::spawn(async move {
+            loop {
+                let msg = task_rx.recv().await;
+                match msg {
+                    Ok(TaskMessage::Kill) | Err(_) => break,
+                    Ok(TaskMessage::DestroyGuild(g)) => {
+                        instance.settings.write().await.remove(&g);
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
 
-        // check if we can actually do what they are asking from us
-        if duration > Duration::from_secs(28 * 60 * 60 * 24) {
-            return FollowupBuilder::new()
-                .description("Duration is too long")
-                .set_ephemeral(anon)
-                .build_command_somehow(&ctx, interaction, shame && !anon)
-                .await;
+    async fn parse_timeout_duration(
+        &self,
+        guild_id: GuildId,
+        from: &str,
+    ) -> std::result::Result<Duration, String> {
+        let max = self
+            .read_settings(&guild_id, |settings| settings.max_timeout)
+            .await
+            .map(|max| max.min(MAX_TIMEOUT))
+            .unwrap_or(MAX_TIMEOUT);
+        match parse_duration(from) {
+            Err(e) => Err(e.to_string()),
+            Ok(duration) if duration > max => Err(format!(
+                "Duration is too long, timeouts can be at most {}",
+                describe_duration(&max)
+            )),
+            Ok(duration) => Ok(duration),
+        }
+    }
+
+    fn parse_ban_duration(from: &str) -> std::result::Result<Duration, String> {
+        match parse_duration(from) {
+            Err(e) => Err(e.to_string()),
+            Ok(duration) if duration > MAX_BAN => Err(format!(
+                "Duration is too long, temporary bans can be at most {}",
+                describe_duration(&MAX_BAN)
+            )),
+            Ok(duration) => Ok(duration),
         }
+    }
+
+    // expands preset names and applies the guild's reason requirement
+    async fn resolve_reason(
+        &self,
+        guild_id: GuildId,
+        given: Option<String>,
+    ) -> std::result::Result<String, String> {
+        self.read_settings(&guild_id, |settings| match &given {
+            Some(given) => Ok(settings
+                .presets
+                .iter()
+                .find(|p| p.name.eq_ignore_ascii_case(given.trim()))
+                .map(|p| p.reason.clone())
+                .unwrap_or_else(|| given.clone())),
+            None if settings.require_reason => Err(s!("This server requires a reason")),
+            None => Ok(s!("No reason given")),
+        })
+        .await
+    }
 
+    // shame, dm and anon flags, falling back to the guild defaults
+    async fn resolve_flags(&self, guild_id: GuildId, args: &SlashMap) -> (bool, bool, bool) {
+        self.read_settings(&guild_id, |settings| {
+            (
+                args.get_boolean("shame").unwrap_or(settings.shame),
+                args.get_boolean("dm").unwrap_or(settings.dm),
+                args.get_boolean("anon").unwrap_or(settings.anon),
+            )
+        })
+        .await
+    }
+
+    /// Checks that we are able to act on `target`, returning a reason if we can't
+    pub fn check_target(
+        ctx: &BotContext,
+        guild_id: GuildId,
+        sanction: &Sanction,
+        target: &User,
+        member: Option<&Member>,
+    ) -> Result<Option<String>> {
         let guild_owner = ctx
             .cache
-            .guild_field(interaction.guild_id.unwrap(), |guild| guild.owner_id)
+            .guild_field(guild_id, |guild| guild.owner_id)
             .ok_or(BotError::CacheMissing)?;
-        if target.user.id == guild_owner {
-            return FollowupBuilder::new()
-                .description("Can't time out owner")
-                .set_ephemeral(anon)
-                .build_command_somehow(&ctx, interaction, shame && !anon)
-                .await;
+        if target.id == guild_owner {
+            return Ok(Some(format!("Can't {} owner", sanction.infinitive())));
         }
 
+        let member = match member {
+            Some(member) => member,
+            None if sanction.needs_member() => {
+                return Ok(Some(format!("{} is not in this server", target.mention())))
+            }
+            None => return Ok(None),
+        };
+
         let roles = ctx
             .cache
-            .guild_roles(interaction.guild_id.unwrap())
+            .guild_roles(guild_id)
             .ok_or(BotError::CacheMissing)?;
         let our_position = ctx
             .cache
-            .member_field(
-                interaction.guild_id.unwrap(),
-                ctx.cache.current_user_id(),
-                |member| highest_role(&roles, &member.roles),
-            )
+            .member_field(guild_id, ctx.cache.current_user_id(), |member| {
+                highest_role(&roles, &member.roles)
+            })
             .ok_or(BotError::CacheMissing)?;
-        let target_position = highest_role(&roles, &target.roles);
+        let target_position = highest_role(&roles, &member.roles);
 
         if our_position <= target_position {
-            return FollowupBuilder::new()
-                .description(format!("{} has a role above me", target.mention()))
-                .set_ephemeral(anon)
-                .build_command_somehow(&ctx, interaction, shame && !anon)
-                .await;
+            return Ok(Some(format!("{} has a role above me", target.mention())));
         }
 
-        // it's actually kinda nice that, with discord's timeout feature, this is all i need to write out to do the actual changes
-        target
-            .edit(&ctx, |e| e.disable_communication_until_datetime(until))
-            .await?;
+        Ok(None)
+    }
 
-        let until = format!(
-            "<t:{0}:{1}> <t:{0}:R>",
-            until.unix_timestamp(),
-            if duration <= Duration::from_secs(60 * 60) {
-                "t"
-            } else {
-                "f"
+    /// Checks that both we and the moderator may hand out `role`, returning a reason if not
+    fn check_role(
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        role: &Role,
+    ) -> Result<Option<String>> {
+        let guild_id = interaction.guild_id.unwrap();
+        if role.managed || role.id.0 == guild_id.0 {
+            return Ok(Some(format!("{} can't be given out", role.mention())));
+        }
+
+        let (roles, owner) = ctx
+            .cache
+            .guild_field(guild_id, |guild| (guild.roles.clone(), guild.owner_id))
+            .ok_or(BotError::CacheMissing)?;
+        let our_position = ctx
+            .cache
+            .member_field(guild_id, ctx.cache.current_user_id(), |member| {
+                highest_role(&roles, &member.roles)
+            })
+            .ok_or(BotError::CacheMissing)?;
+        if our_position <= role.position {
+            return Ok(Some(format!("{} is above my highest role", role.mention())));
+        }
+
+        // same rule discord applies, so this can't be used to hand out roles above yourself
+        let moderator = interaction.member.as_ref().ok_or(BotError::CacheMissing)?;
+        if interaction.user.id != owner && highest_role(&roles, &moderator.roles) <= role.position {
+            return Ok(Some(format!(
+                "{} is not below your highest role",
+                role.mention()
+            )));
+        }
+
+        Ok(None)
+    }
+
+    async fn apply_sanction(
+        ctx: &BotContext,
+        guild_id: GuildId,
+        enforcement: &Enforcement,
+        until: Option<Timestamp>,
+    ) -> Result<()> {
+        match enforcement.sanction {
+            Sanction::Timeout => {
+                // it's actually kinda nice that, with discord's timeout feature, this is all i need to write out to do the actual changes
+                enforcement
+                    .member
+                    .as_ref()
+                    .ok_or(BotError::Internal(14))?
+                    .edit(&ctx, |e| {
+                        e.disable_communication_until_datetime(until.unwrap())
+                    })
+                    .await?;
+            }
+            Sanction::Kick => {
+                guild_id
+                    .kick_with_reason(&ctx, enforcement.target.id, &enforcement.reason)
+                    .await?;
+            }
+            Sanction::Ban {
+                delete_message_days,
+            } => {
+                guild_id
+                    .ban_with_reason(
+                        &ctx,
+                        enforcement.target.id,
+                        delete_message_days,
+                        &enforcement.reason,
+                    )
+                    .await?;
             }
+            Sanction::Softban {
+                delete_message_days,
+            } => {
+                guild_id
+                    .ban_with_reason(
+                        &ctx,
+                        enforcement.target.id,
+                        delete_message_days,
+                        &enforcement.reason,
+                    )
+                    .await?;
+                guild_id.unban(&ctx, enforcement.target.id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_dm(
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        enforcement: &Enforcement,
+        until: &Option<String>,
+    ) -> Result<DmStatus> {
+        let guild = ctx
+            .cache
+            .guild(interaction.guild_id.unwrap())
+            .ok_or(BotError::CacheMissing)?;
+        let result = enforcement
+            .target
+            .dm(&ctx, |m| {
+                m.embed(|e| {
+                    e.description(format!(
+                        "You were {} in {}{} for {}",
+                        enforcement.sanction.past_tense(),
+                        link_guild(&guild, &interaction.channel_id),
+                        match until {
+                            Some(until) => format!(" until {}", until),
+                            None => "".to_string(),
+                        },
+                        enforcement.reason
+                    ))
+                })
+            })
+            .await;
+
+        Ok(match result {
+            Ok(_) => DmStatus::Delivered,
+            // "Cannot send messages to this user"
+            Err(SerenityError::Http(e)) if matches!(&*e, HttpError::UnsuccessfulRequest(r) if r.error.code == 50007) => {
+                DmStatus::Closed
+            }
+            Err(e) => {
+                warn!("Failed to DM {}: {:?}", enforcement.target.id, e);
+                DmStatus::Failed
+            }
+        })
+    }
+
+    /// Mirrors a case to the guild's mod-log channel, if one is set
+    ///
+    /// This always names the moderator, even for anonymous actions
+    async fn mod_log(&self, ctx: &BotContext, case: &NewCase, case_id: i32, channel_id: ChannelId) {
+        let log_channel = match self
+            .read_settings(&case.guild_id, |settings| settings.log_channel)
+            .await
+        {
+            Some(s) => s,
+            None => return,
+        };
+
+        let mut embed = CreateEmbed::default();
+        embed
+            .title(format!("Case #{} | {}", case_id, case.action.as_display()))
+            .field(
+                "Target",
+                format!("{} ({})", case.target.mention(), case.target),
+                true,
+            )
+            .field("Moderator", case.moderator.mention(), true)
+            .field("Channel", channel_id.mention(), true)
+            .field(
+                "Reason",
+                case.reason.as_deref().unwrap_or("*No reason*"),
+                false,
+            );
+        if let Some(duration) = &case.duration {
+            embed.field("Duration", display_duration(duration), true);
+        }
+        embed.field(
+            "DM",
+            match case.dm_status {
+                Some(status) => status.as_display(),
+                None => "Not sent",
+            },
+            true,
         );
+        if case.anon {
+            embed.footer(|f| f.text("Anonymous"));
+        }
+
+        if let Err(e) = log_channel.send_message(&ctx, |m| m.set_embed(embed)).await {
+            warn!(
+                "Failed to send mod log for guild {}: {:?}",
+                case.guild_id, e
+            );
+        }
+    }
+
+    /// Applies an already checked sanction, DMs the target and records the case
+    async fn carry_out(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        enforcement: &Enforcement,
+    ) -> Result<Outcome> {
+        let guild_id = interaction.guild_id.unwrap();
+
+        // worked out up front, so a bad duration fails before anything has happened
+        let lift_at = match enforcement.duration {
+            Some(duration) => Some(Utc::now() + chrono::Duration::from_std(duration)?),
+            None => None,
+        };
+        let until = match lift_at {
+            Some(lift_at) => Some(Timestamp::from_unix_timestamp(lift_at.timestamp())?),
+            None => None,
+        };
+        let until_display = until.map(|until| {
+            format!(
+                "<t:{0}:{1}> <t:{0}:R>",
+                until.unix_timestamp(),
+                if enforcement.duration.unwrap() <= Duration::from_secs(60 * 60) {
+                    "t"
+                } else {
+                    "f"
+                }
+            )
+        });
+
+        let dm = enforcement.dm && !enforcement.target.bot;
+        let mut dm_status = None;
+        if dm && enforcement.sanction.dm_first() {
+            dm_status = Some(Self::send_dm(ctx, interaction, enforcement, &until_display).await?);
+        }
+
+        // a new ban replaces any earlier temporary one, so its unban must not fire early
+        if let Sanction::Ban { .. } = enforcement.sanction {
+            cancel_job(
+                &ctx.pool,
+                guild_id,
+                &Job::Unban {
+                    user: enforcement.target.id,
+                },
+            )
+            .await?;
+        }
+
+        Self::apply_sanction(ctx, guild_id, enforcement, until).await?;
+
+        // roles given with a timeout are taken away again by the job worker
+        if let (Some(role), Some(lift_at)) = (&enforcement.role, lift_at) {
+            let job = Job::RemoveRole {
+                user: enforcement.target.id,
+                role: role.id,
+            };
+            ctx.http
+                .add_member_role(
+                    guild_id.0,
+                    enforcement.target.id.0,
+                    role.id.0,
+                    Some(&enforcement.reason),
+                )
+                .await?;
+            // an earlier removal of the same role would cut this one short
+            cancel_job(&ctx.pool, guild_id, &job).await?;
+            schedule_job(&ctx.pool, guild_id, lift_at, &job).await?;
+        }
+
+        if dm && !enforcement.sanction.dm_first() {
+            dm_status = Some(Self::send_dm(ctx, interaction, enforcement, &until_display).await?);
+        }
+
+        // temporary bans get lifted by the job worker
+        if let (Sanction::Ban { .. }, Some(lift_at)) = (enforcement.sanction, lift_at) {
+            schedule_job(
+                &ctx.pool,
+                guild_id,
+                lift_at,
+                &Job::Unban {
+                    user: enforcement.target.id,
+                },
+            )
+            .await?;
+        }
+
+        let case = NewCase {
+            guild_id,
+            action: enforcement.sanction.case_action(),
+            target: enforcement.target.id,
+            moderator: interaction.user.id,
+            reason: Some(enforcement.reason.clone()),
+            duration: enforcement.duration,
+            anon: enforcement.anon,
+            shame: enforcement.shame,
+            dm,
+            dm_status,
+        };
+        let case_id = case.record(&ctx.pool).await?;
+        self.mod_log(ctx, &case, case_id, interaction.channel_id)
+            .await;
+
+        Ok(Outcome {
+            case_id,
+            until: until_display,
+            dm_status,
+        })
+    }
+
+    /// Shared moderation pipeline: checks, the action itself, case, shame message and DM
+    pub async fn enforce(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        enforcement: Enforcement,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+        let (shame, anon) = (enforcement.shame, enforcement.anon);
+        let followup = shame && !anon;
+
+        if followup {
+            interaction.defer(ctx).await?;
+        }
+
+        // check if we can actually do what they are asking from us
+        if let Some(problem) = Self::check_target(
+            ctx,
+            guild_id,
+            &enforcement.sanction,
+            &enforcement.target,
+            enforcement.member.as_ref(),
+        )? {
+            return FollowupBuilder::new()
+                .description(problem)
+                .set_ephemeral(anon)
+                .build_command_somehow(&ctx, interaction, followup)
+                .await;
+        }
+        if let Some(role) = &enforcement.role {
+            if let Some(problem) = Self::check_role(ctx, interaction, role)? {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .set_ephemeral(anon)
+                    .build_command_somehow(&ctx, interaction, followup)
+                    .await;
+            }
+        }
+
+        let outcome = self.carry_out(ctx, interaction, &enforcement).await?;
+        let case_id = outcome.case_id;
+        let until_display = outcome.until;
 
         if shame {
             let mut create_embed = CreateEmbed::default();
             create_embed
-                .description(format!("{} was muted", target.mention()))
-                .field("Reason", &reason, false)
-                .field("Until", &until, false);
+                .description(format!(
+                    "{} was {}",
+                    enforcement.target.mention(),
+                    enforcement.sanction.past_tense()
+                ))
+                .field("Reason", &enforcement.reason, false);
+            if let Some(until) = &until_display {
+                create_embed.field("Until", until, false);
+            }
+            create_embed.footer(|f| f.text(format!("Case #{}", case_id)));
 
             if anon {
                 interaction
@@ -135,57 +717,1163 @@ impl UtilsModule {
             }
         }
 
-        if dm && !target.user.bot {
-            let guild = ctx
-                .cache
-                .guild(interaction.guild_id.unwrap())
-                .ok_or(BotError::CacheMissing)?;
-            // TODO: properly handle reporting errors, marking if the user has blocked the bot (make sure to handle anon)
-            target
-                .user
-                .dm(&ctx, |m| {
-                    m.embed(|e| {
-                        e.description(format!(
-                            "You were muted in {} until {} for {}",
-                            link_guild(&guild, &interaction.channel_id),
-                            until,
-                            reason
-                        ))
-                    })
-                })
-                .await
-                .ok();
-        }
-
-        if !shame {
+        // the moderator always hears about the dm, privately if the action was anonymous
+        let notified = match outcome.dm_status {
+            None => String::new(),
+            Some(DmStatus::Delivered) => s!("\nUser was notified"),
+            Some(status) => format!("\nUser was not notified: {}", status.as_display()),
+        };
+        if !followup {
             FollowupBuilder::new()
-                .description("Success")
+                .description(format!("Success, created case #{}{}", case_id, notified))
                 .set_ephemeral(anon)
                 .build_command_response(&ctx, interaction)
                 .await?;
+        } else if !matches!(outcome.dm_status, None | Some(DmStatus::Delivered)) {
+            FollowupBuilder::new()
+                .description(notified.trim_start())
+                .ephemeral()
+                .build_command_followup(&ctx, interaction)
+                .await?;
         }
 
         Ok(())
     }
 
-    pub async fn untimeout_command(
+    pub async fn timeout_command(
         &self,
         ctx: &BotContext,
         interaction: &ApplicationCommandInteraction,
         args: SlashMap,
     ) -> Result<()> {
-        interaction.defer(&ctx).await?;
+        let guild_id = interaction.guild_id.unwrap();
+        let (shame, dm, anon) = self.resolve_flags(guild_id, &args).await;
+        let duration = match self
+            .parse_timeout_duration(guild_id, &args.get_string("duration")?)
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .set_ephemeral(anon)
+                    .build_command_response(&ctx, interaction)
+                    .await;
+            }
+        };
+        let reason = match self
+            .resolve_reason(guild_id, args.get_string("reason").ok())
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .set_ephemeral(anon)
+                    .build_command_response(&ctx, interaction)
+                    .await;
+            }
+        };
 
-        let target = interaction
-            .guild_id
-            .unwrap()
-            .member(&ctx, args.get_user("target")?.id())
-            .await?;
+        let target = guild_id.member(&ctx, args.get_user("target")?.id()).await?;
 
-        target.edit(&ctx, |e| e.enable_communication()).await?;
+        self.enforce(
+            ctx,
+            interaction,
+            Enforcement {
+                sanction: Sanction::Timeout,
+                target: target.user.clone(),
+                member: Some(target),
+                reason,
+                duration: Some(duration),
+                role: args.get_role("role").ok(),
+                shame,
+                dm,
+                anon,
+            },
+        )
+        .await
+    }
+
+    pub async fn remind_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+        let duration = match parse_duration(&args.get_string("duration")?) {
+            Ok(duration) if duration > MAX_REMINDER => Err(format!(
+                "Duration is too long, reminders can be at most {}",
+                describe_duration(&MAX_REMINDER)
+            )),
+            Ok(duration) => Ok(duration),
+            Err(e) => Err(e.to_string()),
+        };
+        let duration = match duration {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .ephemeral()
+                    .build_command_response(&ctx, interaction)
+                    .await;
+            }
+        };
+
+        let pending = pending_jobs(&ctx.pool, guild_id)
+            .await?
+            .into_iter()
+            .filter(|(_, job)| {
+                matches!(job, Job::Reminder { user, .. } if *user == interaction.user.id)
+            })
+            .count();
+        if pending >= MAX_REMINDERS {
+            return FollowupBuilder::new()
+                .description(format!(
+                    "You can have at most {} pending reminders in this server",
+                    MAX_REMINDERS
+                ))
+                .ephemeral()
+                .build_command_response(&ctx, interaction)
+                .await;
+        }
+
+        let run_at = Utc::now() + chrono::Duration::from_std(duration)?;
+        schedule_job(
+            &ctx.pool,
+            guild_id,
+            run_at,
+            &Job::Reminder {
+                channel: interaction.channel_id,
+                user: interaction.user.id,
+                message: args.get_string("message")?,
+            },
+        )
+        .await?;
 
         FollowupBuilder::new()
-            .description("Success")
+            .description(format!("I'll remind you <t:{}:R>", run_at.timestamp()))
+            .ephemeral()
+            .build_command_response(&ctx, interaction)
+            .await
+    }
+
+    pub async fn timeout_many_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+        let (_, dm, _) = self.resolve_flags(guild_id, &args).await;
+        let duration = match self
+            .parse_timeout_duration(guild_id, &args.get_string("duration")?)
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .ephemeral()
+                    .build_command_response(&ctx, interaction)
+                    .await;
+            }
+        };
+        let reason = match self
+            .resolve_reason(guild_id, args.get_string("reason").ok())
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .ephemeral()
+                    .build_command_response(&ctx, interaction)
+                    .await;
+            }
+        };
+
+        let mut targets = Vec::new();
+        for captures in self.user_regex.captures_iter(&args.get_string("targets")?) {
+            if let Some(id) = captures
+                .get(1)
+                .or_else(|| captures.get(2))
+                .and_then(|m| m.as_str().parse::<u64>().ok())
+            {
+                let id = UserId(id);
+                if !targets.contains(&id) {
+                    targets.push(id);
+                }
+            }
+        }
+
+        if targets.is_empty() {
+            return FollowupBuilder::new()
+                .description("No users found, use mentions or IDs")
+                .ephemeral()
+                .build_command_response(&ctx, interaction)
+                .await;
+        }
+        if targets.len() > MAX_BULK_TARGETS {
+            return FollowupBuilder::new()
+                .description(format!(
+                    "Can time out at most {} users at once",
+                    MAX_BULK_TARGETS
+                ))
+                .ephemeral()
+                .build_command_response(&ctx, interaction)
+                .await;
+        }
+
+        interaction.defer(&ctx).await?;
+
+        let mut lines = Vec::with_capacity(targets.len());
+        for target in targets {
+            let member = match guild_id.member(&ctx, target).await {
+                Ok(member) => member,
+                // "Unknown Member"
+                Err(SerenityError::Http(e)) if matches!(&*e, HttpError::UnsuccessfulRequest(r) if r.error.code == 10007) =>
+                {
+                    lines.push(format!("❌ {} is not in this server", target.mention()));
+                    continue;
+                }
+                Err(e) => {
+                    lines.push(format!("❌ {} - {}", target.mention(), e));
+                    continue;
+                }
+            };
+
+            let enforcement = Enforcement {
+                sanction: Sanction::Timeout,
+                target: member.user.clone(),
+                member: Some(member),
+                reason: reason.clone(),
+                duration: Some(duration),
+                role: None,
+                shame: false,
+                dm,
+                anon: false,
+            };
+
+            match Self::check_target(
+                ctx,
+                guild_id,
+                &enforcement.sanction,
+                &enforcement.target,
+                enforcement.member.as_ref(),
+            ) {
+                Ok(None) => {}
+                Ok(Some(problem)) => {
+                    lines.push(format!("❌ {}", problem));
+                    continue;
+                }
+                Err(e) => {
+                    lines.push(format!("❌ {} - {}", target.mention(), e));
+                    continue;
+                }
+            }
+
+            lines.push(match self.carry_out(ctx, interaction, &enforcement).await {
+                Ok(outcome) => format!(
+                    "✅ {} - case #{}{}",
+                    target.mention(),
+                    outcome.case_id,
+                    match outcome.dm_status {
+                        Some(DmStatus::Delivered) | None => "",
+                        Some(_) => ", not notified",
+                    }
+                ),
+                Err(e) => format!("❌ {} - {}", target.mention(), e),
+            });
+        }
+
+        interaction
+            .create_followup_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Bulk timeout")
+                        .description(lines.join("\n"))
+                        .field("Reason", &reason, false)
+                        .field("Duration", display_duration(&duration), true)
+                        .field("By", interaction.user.mention(), true)
+                })
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn duration_autocomplete(
+        &self,
+        ctx: &BotContext,
+        interaction: &AutocompleteInteraction,
+        value: &str,
+        timeout: bool,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let value = value.trim();
+        // bare numbers get a suggestion for every unit
+        let inputs = if value.is_empty() {
+            DURATION_SUGGESTIONS.iter().map(|s| s.to_string()).collect()
+        } else if value.chars().all(|c| c.is_ascii_digit()) {
+            UNIT_SUGGESTIONS
+                .iter()
+                .map(|unit| format!("{}{}", value, unit))
+                .collect()
+        } else {
+            vec![value.to_string()]
+        };
+
+        let mut choices = Vec::new();
+        for input in inputs {
+            let parsed = if timeout {
+                self.parse_timeout_duration(guild_id, &input).await
+            } else {
+                Self::parse_ban_duration(&input)
+            };
+            choices.push(match parsed {
+                // absolute times are kept as typed so they don't drift before the command is sent
+                Ok(duration) if input.to_lowercase().starts_with("until ") => (
+                    format!("{} ({})", input, describe_duration(&duration)),
+                    input,
+                ),
+                Ok(duration) => {
+                    let normalized = display_duration(&duration).replace(' ', "");
+                    (
+                        format!("{} ({})", normalized, describe_duration(&duration)),
+                        normalized,
+                    )
+                }
+                Err(problem) => (format!("⚠️ {}", problem), input),
+            });
+        }
+
+        interaction
+            .create_autocomplete_response(&ctx, |r| {
+                for (name, value) in choices
+                    .into_iter()
+                    .filter(|(name, value)| {
+                        name.chars().count() <= MAX_CHOICE_LENGTH
+                            && value.chars().count() <= MAX_CHOICE_LENGTH
+                    })
+                    .take(MAX_CHOICES)
+                {
+                    r.add_string_choice(name, value);
+                }
+                r
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn reason_autocomplete(
+        &self,
+        ctx: &BotContext,
+        interaction: &AutocompleteInteraction,
+        value: &str,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let value = value.trim();
+        let lower = value.to_lowercase();
+
+        // presets are submitted by name and expanded when the command runs
+        let mut choices = self
+            .read_settings(&guild_id, |settings| {
+                settings
+                    .presets
+                    .iter()
+                    .filter(|p| {
+                        p.name.to_lowercase().contains(&lower)
+                            || p.reason.to_lowercase().contains(&lower)
+                    })
+                    .map(|p| {
+                        (
+                            truncate_choice(format!("{}: {}", p.name, p.reason)),
+                            p.name.clone(),
+                        )
+                    })
+                    .collect::<Vec<(String, String)>>()
+            })
+            .await;
+        let has_presets = !choices.is_empty();
+
+        // then the most used reasons, with the defaults filling up the list if there are no presets
+        let history = sqlx::query(
+            "select reason, count(*) as uses from ModerationCases \
+             where guild_id = $1 and reason is not null and length(reason) <= $3 \
+             and position(lower($2) in lower(reason)) > 0 \
+             group by reason order by uses desc limit $4",
+        )
+        .bind(SqlId(guild_id))
+        .bind(value)
+        .bind(MAX_CHOICE_LENGTH as i32)
+        .bind(MAX_CHOICES as i64)
+        .map(|row: PgRow| row.get::<String, _>("reason"))
+        .fetch_all(&ctx.pool)
+        .await?;
+        let defaults = DEFAULT_REASONS
+            .iter()
+            .filter(|r| !has_presets && r.to_lowercase().contains(&lower))
+            .map(|r| r.to_string());
+
+        for reason in history.into_iter().chain(defaults) {
+            if !choices.iter().any(|(_, v)| v == &reason) {
+                choices.push((reason.clone(), reason));
+            }
+        }
+
+        // always offer what was typed so it can be picked as-is
+        if !value.is_empty()
+            && value.chars().count() <= MAX_CHOICE_LENGTH
+            && !choices.iter().any(|(_, v)| v == value)
+        {
+            choices.insert(0, (value.to_string(), value.to_string()));
+        }
+
+        interaction
+            .create_autocomplete_response(&ctx, |r| {
+                for (name, value) in choices.into_iter().take(MAX_CHOICES) {
+                    r.add_string_choice(name, value);
+                }
+                r
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn preset_autocomplete(
+        &self,
+        ctx: &BotContext,
+        interaction: &AutocompleteInteraction,
+        value: &str,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let lower = value.trim().to_lowercase();
+
+        let choices = self
+            .read_settings(&guild_id, |settings| {
+                settings
+                    .presets
+                    .iter()
+                    .filter(|p| p.name.to_lowercase().contains(&lower))
+                    .map(|p| {
+                        (
+                            truncate_choice(format!("{}: {}", p.name, p.reason)),
+                            p.name.clone(),
+                        )
+                    })
+                    .collect::<Vec<(String, String)>>()
+            })
+            .await;
+
+        interaction
+            .create_autocomplete_response(&ctx, |r| {
+                for (name, value) in choices.into_iter().take(MAX_CHOICES) {
+                    r.add_string_choice(name, value);
+                }
+                r
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn moderation_config_view(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.unwrap();
+
+        let embed = self
+            .read_settings(&guild_id, |settings| {
+                let mut embed = CreateEmbed::default();
+                embed
+                    .title("Moderation settings")
+                    .field(
+                        "Default flags",
+                        format!(
+                            "Shame: {}\nDM: {}\nAnon: {}",
+                            settings.shame, settings.dm, settings.anon
+                        ),
+                        true,
+                    )
+                    .field(
+                        "Max timeout",
+                        describe_duration(
+                            &settings
+                                .max_timeout
+                                .map(|max| max.min(MAX_TIMEOUT))
+                                .unwrap_or(MAX_TIMEOUT),
+                        ),
+                        true,
+                    )
+                    .field("Require reason", settings.require_reason, true)
+                    .field(
+                        "Log channel",
+                        match settings.log_channel {
+                            Some(channel) => channel.mention().to_string(),
+                            None => s!("*None*"),
+                        },
+                        true,
+                    )
+                    .field(
+                        "Reason presets",
+                        if settings.presets.is_empty() {
+                            s!("*None*")
+                        } else {
+                            settings
+                                .presets
+                                .iter()
+                                .map(|p| format!("`{}` {}", p.name, p.reason))
+                                .collect::<Vec<String>>()
+                                .join("\n")
+                        },
+                        false,
+                    );
+                embed
+            })
+            .await;
+
+        interaction
+            .create_followup_message(&ctx, |m| m.add_embed(embed))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn moderation_config_set(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+
+        let max_timeout = match args.get_string("max-duration") {
+            Ok(s) if s.trim().eq_ignore_ascii_case("none") => Some(None),
+            Ok(s) => match parse_duration(&s) {
+                Ok(duration) if duration > MAX_TIMEOUT => {
+                    return FollowupBuilder::new()
+                        .description("Max duration can't be longer than 28 days")
+                        .ephemeral()
+                        .build_command_response(&ctx, interaction)
+                        .await;
+                }
+                Ok(duration) => Some(Some(duration)),
+                Err(e) => {
+                    return FollowupBuilder::new()
+                        .description(e.to_string())
+                        .ephemeral()
+                        .build_command_response(&ctx, interaction)
+                        .await;
+                }
+            },
+            Err(_) => None,
+        };
+
+        // (setting name, before, after) for every value that actually changed
+        let (changes, row) = self
+            .write_settings(&guild_id, |settings| {
+                let mut changes = Vec::new();
+                macro_rules! update_flag {
+                    ($field: ident, $arg: expr, $name: expr) => {
+                        if let Ok(value) = args.get_boolean($arg) {
+                            if settings.$field != value {
+                                changes.push((
+                                    $name,
+                                    settings.$field.to_string(),
+                                    value.to_string(),
+                                ));
+                                settings.$field = value;
+                            }
+                        }
+                    };
+                }
+                update_flag!(shame, "shame", "Default shame");
+                update_flag!(dm, "dm", "Default DM");
+                update_flag!(anon, "anon", "Default anon");
+                update_flag!(require_reason, "require-reason", "Require reason");
+                if let Some(max_timeout) = max_timeout {
+                    if settings.max_timeout != max_timeout {
+                        let display = |d: Option<Duration>| match d {
+                            Some(d) => display_duration(&d),
+                            None => s!("none"),
+                        };
+                        changes.push((
+                            "Max timeout",
+                            display(settings.max_timeout),
+                            display(max_timeout),
+                        ));
+                        settings.max_timeout = max_timeout;
+                    }
+                }
+                (
+                    changes,
+                    (
+                        settings.shame,
+                        settings.dm,
+                        settings.anon,
+                        settings.max_timeout,
+                        settings.require_reason,
+                    ),
+                )
+            })
+            .await;
+
+        if changes.is_empty() {
+            return FollowupBuilder::new()
+                .description("Nothing changed")
+                .ephemeral()
+                .build_command_response(&ctx, interaction)
+                .await;
+        }
+
+        sqlx::query(
+            "insert into ModerationSettings (guild_id, default_shame, default_dm, default_anon, max_timeout, require_reason) \
+             values ($1, $2, $3, $4, $5, $6) on conflict on constraint moderation_settings_idx do update \
+             set default_shame = $2, default_dm = $3, default_anon = $4, max_timeout = $5, require_reason = $6",
+        )
+        .bind(SqlId(guild_id))
+        .bind(row.0)
+        .bind(row.1)
+        .bind(row.2)
+        .bind(row.3.map(|d| d.as_secs() as i64))
+        .bind(row.4)
+        .execute(&ctx.pool)
+        .await?;
+
+        for (name, before, after) in &changes {
+            AuditEntry {
+                guild_id,
+                actor: interaction.user.id,
+                action: AuditAction::ModerationSetting,
+                target: name.to_string(),
+                before: Some(before.clone()),
+                after: Some(after.clone()),
+            }
+            .record(&ctx.pool)
+            .await?;
+        }
+
+        FollowupBuilder::new()
+            .description(
+                changes
+                    .iter()
+                    .map(|(name, before, after)| format!("{}: {} → {}", name, before, after))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            )
+            .build_command_response(&ctx, interaction)
+            .await
+    }
+
+    pub async fn export_config(&self, guild_id: &GuildId) -> ModerationSection {
+        self.read_settings(guild_id, |settings| ModerationSection {
+            shame: settings.shame,
+            dm: settings.dm,
+            anon: settings.anon,
+            max_timeout: settings.max_timeout.map(|d| d.as_secs()),
+            require_reason: settings.require_reason,
+            log_channel: settings.log_channel,
+            presets: settings
+                .presets
+                .iter()
+                .map(|preset| PresetSection {
+                    name: preset.name.clone(),
+                    reason: preset.reason.clone(),
+                })
+                .collect(),
+        })
+        .await
+    }
+
+    /// Problems with an imported configuration that the commands would have refused
+    pub fn validate_config(config: &ModerationSection) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Some(max_timeout) = config.max_timeout {
+            if Duration::from_secs(max_timeout) > MAX_TIMEOUT {
+                problems.push(s!("Max timeout can't be longer than 28 days"));
+            }
+        }
+        if config.presets.len() > MAX_PRESETS {
+            problems.push(format!("Can't have more than {} presets", MAX_PRESETS));
+        }
+        for preset in &config.presets {
+            if preset.name.is_empty() || preset.name.chars().count() > MAX_PRESET_NAME_LENGTH {
+                problems.push(format!(
+                    "Preset name `{}` must be 1 to {} characters",
+                    preset.name, MAX_PRESET_NAME_LENGTH
+                ));
+            }
+        }
+        let mut names = config
+            .presets
+            .iter()
+            .map(|preset| preset.name.to_ascii_lowercase())
+            .collect::<Vec<String>>();
+        names.sort();
+        if names.windows(2).any(|pair| pair[0] == pair[1]) {
+            problems.push(s!("Preset names must be unique"));
+        }
+        problems
+    }
+
+    pub async fn import_config(
+        &self,
+        conn: &mut PgConnection,
+        guild_id: &GuildId,
+        config: &ModerationSection,
+    ) -> Result<()> {
+        sqlx::query(
+            "insert into ModerationSettings (guild_id, default_shame, default_dm, default_anon, max_timeout, require_reason, log_channel) \
+             values ($1, $2, $3, $4, $5, $6, $7) on conflict on constraint moderation_settings_idx do update \
+             set default_shame = $2, default_dm = $3, default_anon = $4, max_timeout = $5, require_reason = $6, log_channel = $7",
+        )
+        .bind(SqlId(*guild_id))
+        .bind(config.shame)
+        .bind(config.dm)
+        .bind(config.anon)
+        .bind(config.max_timeout.map(|d| d as i64))
+        .bind(config.require_reason)
+        .bind(config.log_channel.map(SqlId))
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query("delete from ReasonPresets where guild_id = $1")
+            .bind(SqlId(*guild_id))
+            .execute(&mut *conn)
+            .await?;
+        for preset in &config.presets {
+            sqlx::query("insert into ReasonPresets (guild_id, name, reason) values ($1, $2, $3)")
+                .bind(SqlId(*guild_id))
+                .bind(&preset.name)
+                .bind(&preset.reason)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Puts an imported configuration in the cache, once it's been saved
+    pub async fn cache_config(&self, guild_id: &GuildId, config: &ModerationSection) {
+        self.write_settings(guild_id, |settings| {
+            settings.shame = config.shame;
+            settings.dm = config.dm;
+            settings.anon = config.anon;
+            settings.max_timeout = config.max_timeout.map(Duration::from_secs);
+            settings.require_reason = config.require_reason;
+            settings.log_channel = config.log_channel;
+            settings.presets = config
+                .presets
+                .iter()
+                .map(|preset| ReasonPreset {
+                    name: preset.name.clone(),
+                    reason: preset.reason.clone(),
+                })
+                .collect();
+        })
+        .await
+    }
+
+    pub async fn moderation_config_log_channel(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        defer_command(&ctx, interaction).await?;
+        let guild_id = interaction.guild_id.unwrap();
+        let target = args.get_channel("target").ok().map(|c| c.id);
+
+        let before = self
+            .write_settings(&guild_id, |settings| {
+                std::mem::replace(&mut settings.log_channel, target)
+            })
+            .await;
+
+        sqlx::query(
+            "insert into ModerationSettings (guild_id, log_channel) values ($1, $2) \
+             on conflict on constraint moderation_settings_idx do update set log_channel = $2",
+        )
+        .bind(SqlId(guild_id))
+        .bind(target.map(SqlId))
+        .execute(&ctx.pool)
+        .await?;
+
+        if before != target {
+            AuditEntry {
+                guild_id,
+                actor: interaction.user.id,
+                action: AuditAction::ModerationLogChannel,
+                target: s!("Moderation log channel"),
+                before: before.map(|c| c.mention().to_string()),
+                after: target.map(|c| c.mention().to_string()),
+            }
+            .record(&ctx.pool)
+            .await?;
+        }
+
+        FollowupBuilder::new()
+            .description("Success")
+            .build_command_followup(&ctx, interaction)
+            .await
+    }
+
+    pub async fn channel_delete(&self, ctx: &BotContext, channel: &GuildChannel) -> Result<()> {
+        // a deleted log channel can't receive anything anymore
+        let removed = self
+            .write_settings(&channel.guild_id, |settings| {
+                if settings.log_channel == Some(channel.id) {
+                    settings.log_channel = None;
+                    true
+                } else {
+                    false
+                }
+            })
+            .await;
+        if removed {
+            sqlx::query("update ModerationSettings set log_channel = null where guild_id = $1")
+                .bind(SqlId(channel.guild_id))
+                .execute(&ctx.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn moderation_preset_add(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+        let name = args.get_string("name")?.trim().to_string();
+        let reason = args.get_string("reason")?;
+
+        if name.is_empty() || name.chars().count() > MAX_PRESET_NAME_LENGTH {
+            return FollowupBuilder::new()
+                .description(format!(
+                    "Preset names must be between 1 and {} characters",
+                    MAX_PRESET_NAME_LENGTH
+                ))
+                .ephemeral()
+                .build_command_response(&ctx, interaction)
+                .await;
+        }
+
+        let problem = self
+            .write_settings(&guild_id, |settings| {
+                if settings
+                    .presets
+                    .iter()
+                    .any(|p| p.name.eq_ignore_ascii_case(&name))
+                {
+                    Some(format!("Preset `{}` already exists", name))
+                } else if settings.presets.len() >= MAX_PRESETS {
+                    Some(format!("Can't have more than {} presets", MAX_PRESETS))
+                } else {
+                    settings.presets.push(ReasonPreset {
+                        name: name.clone(),
+                        reason: reason.clone(),
+                    });
+                    None
+                }
+            })
+            .await;
+
+        if let Some(problem) = problem {
+            return FollowupBuilder::new()
+                .description(problem)
+                .ephemeral()
+                .build_command_response(&ctx, interaction)
+                .await;
+        }
+
+        sqlx::query("insert into ReasonPresets (guild_id, name, reason) values ($1, $2, $3)")
+            .bind(SqlId(guild_id))
+            .bind(&name)
+            .bind(&reason)
+            .execute(&ctx.pool)
+            .await?;
+
+        AuditEntry {
+            guild_id,
+            actor: interaction.user.id,
+            action: AuditAction::ReasonPresetAdd,
+            target: name.clone(),
+            before: None,
+            after: Some(reason),
+        }
+        .record(&ctx.pool)
+        .await?;
+
+        FollowupBuilder::new()
+            .description(format!("Added preset `{}`", name))
+            .build_command_response(&ctx, interaction)
+            .await
+    }
+
+    pub async fn moderation_preset_remove(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+        let name = args.get_string("name")?;
+
+        let removed = self
+            .write_settings(&guild_id, |settings| {
+                let index = settings
+                    .presets
+                    .iter()
+                    .position(|p| p.name.eq_ignore_ascii_case(name.trim()))?;
+                Some(settings.presets.remove(index))
+            })
+            .await;
+
+        let removed = match removed {
+            Some(s) => s,
+            None => return Err(Error::new(BotError::NotFound(format!("Preset `{}`", name)))),
+        };
+
+        sqlx::query("delete from ReasonPresets where guild_id = $1 and name = $2")
+            .bind(SqlId(guild_id))
+            .bind(&removed.name)
+            .execute(&ctx.pool)
+            .await?;
+
+        AuditEntry {
+            guild_id,
+            actor: interaction.user.id,
+            action: AuditAction::ReasonPresetRemove,
+            target: removed.name.clone(),
+            before: Some(removed.reason),
+            after: None,
+        }
+        .record(&ctx.pool)
+        .await?;
+
+        FollowupBuilder::new()
+            .description(format!("Removed preset `{}`", removed.name))
+            .build_command_response(&ctx, interaction)
+            .await
+    }
+
+    pub async fn untimeout_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        interaction.defer(&ctx).await?;
+
+        let guild_id = interaction.guild_id.unwrap();
+        let reason = match self
+            .resolve_reason(guild_id, args.get_string("reason").ok())
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .build_command_followup(&ctx, interaction)
+                    .await;
+            }
+        };
+        let target = guild_id.member(&ctx, args.get_user("target")?.id()).await?;
+
+        target.edit(&ctx, |e| e.enable_communication()).await?;
+
+        // roles that came with the timeout go with it
+        for (id, job) in pending_jobs(&ctx.pool, guild_id).await? {
+            if matches!(job, Job::RemoveRole { user, .. } if user == target.user.id) {
+                expedite_job(&ctx.pool, id).await?;
+            }
+        }
+
+        let case = NewCase {
+            guild_id,
+            action: CaseAction::Untimeout,
+            target: target.user.id,
+            moderator: interaction.user.id,
+            reason: Some(reason),
+            duration: None,
+            anon: false,
+            shame: false,
+            dm: false,
+            dm_status: None,
+        };
+        let case_id = case.record(&ctx.pool).await?;
+        self.mod_log(ctx, &case, case_id, interaction.channel_id)
+            .await;
+
+        FollowupBuilder::new()
+            .description(format!("Success, created case #{}", case_id))
+            .build_command_followup(&ctx, interaction)
+            .await
+    }
+
+    pub async fn kick_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+        let (shame, dm, anon) = self.resolve_flags(guild_id, &args).await;
+        let reason = match self
+            .resolve_reason(guild_id, args.get_string("reason").ok())
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .set_ephemeral(anon)
+                    .build_command_response(&ctx, interaction)
+                    .await;
+            }
+        };
+
+        let target = args.get_user("target")?;
+        let member = guild_id.member(&ctx, target.id()).await.ok();
+
+        self.enforce(
+            ctx,
+            interaction,
+            Enforcement {
+                sanction: Sanction::Kick,
+                target: target.get_user().clone(),
+                member,
+                reason,
+                duration: None,
+                role: None,
+                shame,
+                dm,
+                anon,
+            },
+        )
+        .await
+    }
+
+    pub async fn ban_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+        softban: bool,
+    ) -> Result<()> {
+        let guild_id = interaction.guild_id.unwrap();
+        let (shame, dm, anon) = self.resolve_flags(guild_id, &args).await;
+        let duration = match args.get_string("duration") {
+            Ok(duration) if !softban => match Self::parse_ban_duration(&duration) {
+                Ok(s) => Some(s),
+                Err(problem) => {
+                    return FollowupBuilder::new()
+                        .description(problem)
+                        .set_ephemeral(anon)
+                        .build_command_response(&ctx, interaction)
+                        .await;
+                }
+            },
+            _ => None,
+        };
+        let delete_message_days = args
+            .get_integer("delete-days")
+            .unwrap_or(if softban { 1 } else { 0 })
+            .clamp(0, 7) as u8;
+        let reason = match self
+            .resolve_reason(guild_id, args.get_string("reason").ok())
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .set_ephemeral(anon)
+                    .build_command_response(&ctx, interaction)
+                    .await;
+            }
+        };
+
+        let target = args.get_user("target")?;
+        let member = guild_id.member(&ctx, target.id()).await.ok();
+
+        self.enforce(
+            ctx,
+            interaction,
+            Enforcement {
+                sanction: if softban {
+                    Sanction::Softban {
+                        delete_message_days,
+                    }
+                } else {
+                    Sanction::Ban {
+                        delete_message_days,
+                    }
+                },
+                target: target.get_user().clone(),
+                member,
+                reason,
+                duration,
+                role: None,
+                shame,
+                dm,
+                anon,
+            },
+        )
+        .await
+    }
+
+    pub async fn unban_command(
+        &self,
+        ctx: &BotContext,
+        interaction: &ApplicationCommandInteraction,
+        args: SlashMap,
+    ) -> Result<()> {
+        interaction.defer(&ctx).await?;
+
+        let guild_id = interaction.guild_id.unwrap();
+        let target = args.get_user("target")?;
+        let reason = match self
+            .resolve_reason(guild_id, args.get_string("reason").ok())
+            .await
+        {
+            Ok(s) => s,
+            Err(problem) => {
+                return FollowupBuilder::new()
+                    .description(problem)
+                    .build_command_followup(&ctx, interaction)
+                    .await;
+            }
+        };
+
+        guild_id.unban(&ctx, *target.id()).await?;
+        cancel_job(&ctx.pool, guild_id, &Job::Unban { user: *target.id() }).await?;
+
+        let case = NewCase {
+            guild_id,
+            action: CaseAction::Unban,
+            target: *target.id(),
+            moderator: interaction.user.id,
+            reason: Some(reason),
+            duration: None,
+            anon: false,
+            shame: false,
+            dm: false,
+            dm_status: None,
+        };
+        let case_id = case.record(&ctx.pool).await?;
+        self.mod_log(ctx, &case, case_id, interaction.channel_id)
+            .await;
+
+        FollowupBuilder::new()
+            .description(format!("Success, created case #{}", case_id))
             .build_command_followup(&ctx, interaction)
             .await
     }
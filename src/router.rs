@@ -7,10 +7,11 @@ use crate::custom_ids::{parse_custom_id, CustomIdType};
 use crate::debug;
 use crate::decode;
 use crate::handler::Handler;
-use crate::modules::PermissionType;
+use crate::modules::{channel_scopes, Grant, PermissionType};
 use crate::prelude::*;
 use anyhow::{Error, Result};
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::interaction::autocomplete::AutocompleteInteraction;
 use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
 use serenity::model::prelude::interaction::{InteractionResponseType, MessageFlags};
 use serenity::utils::Color;
@@ -24,38 +25,59 @@ macro_rules! ensure_guild {
     };
 }
 
+// evaluates to the missing permission of the invoking member, if any
+macro_rules! missing_permission {
+    ($ctx: expr, $cache: expr, $interaction: expr, $permission: ident) => {{
+        let guild_id = $interaction.guild_id.ok_or(BotError::GuildOnly)?;
+        let roles = &$interaction
+            .member
+            .as_ref()
+            .ok_or(BotError::CacheMissing)?
+            .roles;
+        let mut upgraded_roles = Vec::new();
+        let owner = $ctx
+            .cache
+            .guild_field(&guild_id, |g| {
+                for role in roles {
+                    match g.roles.get(&role) {
+                        Some(data) => upgraded_roles.push(data.clone()),
+                        None => {}
+                    }
+                }
+                g.owner_id
+            })
+            .ok_or(BotError::CacheMissing)?;
+        let scopes = channel_scopes($ctx, guild_id, $interaction.channel_id);
+        $cache
+            .check(
+                &PermissionType::$permission,
+                &guild_id,
+                &owner,
+                &$interaction.user.id,
+                &upgraded_roles,
+                &scopes,
+            )
+            .await
+    }};
+}
+
+// instance level commands are reserved for whoever runs the bot
+macro_rules! ensure_owner {
+    ($handler: expr, $interaction: expr, $command: expr) => {
+        if $interaction.user.id == $handler.owner_id {
+            $command
+        } else {
+            Err(Error::new(BotError::Generic(s!(
+                "Only the bot owner can use this command"
+            ))))
+        }
+    };
+}
+
 macro_rules! ensure_permission_base {
     ($ctx: expr, $cache: expr, $interaction: expr, $application_id: expr, $permission: ident, $command: expr) => {
         ensure_guild!($interaction, {
-            let guild_id = $interaction.guild_id.unwrap();
-            let roles = &$interaction
-                .member
-                .as_ref()
-                .ok_or(BotError::CacheMissing)?
-                .roles;
-            let mut upgraded_roles = Vec::new();
-            let owner = $ctx
-                .cache
-                .guild_field(&guild_id, |g| {
-                    for role in roles {
-                        match g.roles.get(&role) {
-                            Some(data) => upgraded_roles.push(data.clone()),
-                            None => {}
-                        }
-                    }
-                    g.owner_id
-                })
-                .ok_or(BotError::CacheMissing)?;
-            if let Some(missing) = $cache
-                .check(
-                    &PermissionType::$permission,
-                    &guild_id,
-                    &owner,
-                    &$interaction.user.id,
-                    &upgraded_roles,
-                )
-                .await
-            {
+            if let Some(missing) = missing_permission!($ctx, $cache, $interaction, $permission) {
                 $interaction
                     .create_interaction_response($ctx, |r| {
                         r.kind(InteractionResponseType::ChannelMessageWithSource)
@@ -102,6 +124,10 @@ pub async fn chat_input_router(
 
     match path.as_str() {
         "info" => handler.updates.info_command(ctx, interaction).await,
+        "audit" => ensure_permission!(
+            ViewAuditLog,
+            handler.audit.audit_command(ctx, interaction).await
+        ),
         "permissions list" => ensure_permission!(
             ManagePermissions,
             handler.permissions.permissions_list(ctx, interaction).await
@@ -113,18 +139,53 @@ pub async fn chat_input_router(
                 .permissions_set(ctx, interaction, args)
                 .await
         ),
+        "permissions reset" => ensure_permission!(
+            ManagePermissions,
+            handler
+                .permissions
+                .permissions_reset(ctx, interaction, args)
+                .await
+        ),
+        "permissions keep-user-grants" => ensure_permission!(
+            ManagePermissions,
+            handler
+                .permissions
+                .permissions_keep_user_grants(ctx, interaction, args)
+                .await
+        ),
+        "permissions check" => ensure_permission!(
+            ManagePermissions,
+            handler
+                .permissions
+                .permissions_check(ctx, interaction, args)
+                .await
+        ),
         "permissions add" => ensure_permission!(
             ManagePermissions,
             handler
                 .permissions
-                .permissions_add(ctx, interaction, args)
+                .permissions_add(ctx, interaction, args, Grant::Allow)
                 .await
         ),
         "permissions remove" => ensure_permission!(
             ManagePermissions,
             handler
                 .permissions
-                .permissions_remove(ctx, interaction, args)
+                .permissions_remove(ctx, interaction, args, Grant::Allow)
+                .await
+        ),
+        "permissions deny" => ensure_permission!(
+            ManagePermissions,
+            handler
+                .permissions
+                .permissions_add(ctx, interaction, args, Grant::Deny)
+                .await
+        ),
+        "permissions undeny" => ensure_permission!(
+            ManagePermissions,
+            handler
+                .permissions
+                .permissions_remove(ctx, interaction, args, Grant::Deny)
                 .await
         ),
         "previews add" => ensure_permission!(
@@ -149,11 +210,33 @@ pub async fn chat_input_router(
                 .previews_archive(ctx, interaction, args)
                 .await
         ),
+        "previews suppress-embeds" => ensure_permission!(
+            ManagePreviews,
+            handler
+                .previews
+                .previews_suppress_embeds(ctx, interaction, args)
+                .await
+        ),
+        "previews live" => ensure_permission!(
+            ManagePreviews,
+            handler.previews.previews_live(ctx, interaction, args).await
+        ),
         "previews view" => handler.previews.previews_view(ctx, interaction, args).await,
         "timeout" => ensure_permission!(
             Timeout,
             handler.utils.timeout_command(ctx, interaction, args).await
         ),
+        "timeout-many" => ensure_permission!(
+            Timeout,
+            handler
+                .utils
+                .timeout_many_command(ctx, interaction, args)
+                .await
+        ),
+        "remind" => ensure_guild!(
+            interaction,
+            handler.utils.remind_command(ctx, interaction, args).await
+        ),
         "untimeout" => ensure_permission!(
             Timeout,
             handler
@@ -161,6 +244,104 @@ pub async fn chat_input_router(
                 .untimeout_command(ctx, interaction, args)
                 .await
         ),
+        "kick" => ensure_permission!(
+            Kick,
+            handler.utils.kick_command(ctx, interaction, args).await
+        ),
+        "ban" => ensure_permission!(
+            Ban,
+            handler
+                .utils
+                .ban_command(ctx, interaction, args, false)
+                .await
+        ),
+        "softban" => ensure_permission!(
+            Ban,
+            handler
+                .utils
+                .ban_command(ctx, interaction, args, true)
+                .await
+        ),
+        "unban" => ensure_permission!(
+            Ban,
+            handler.utils.unban_command(ctx, interaction, args).await
+        ),
+        "moderation config view" => ensure_permission!(
+            Administrator,
+            handler.utils.moderation_config_view(ctx, interaction).await
+        ),
+        "moderation config set" => ensure_permission!(
+            Administrator,
+            handler
+                .utils
+                .moderation_config_set(ctx, interaction, args)
+                .await
+        ),
+        "moderation config log-channel" => ensure_permission!(
+            Administrator,
+            handler
+                .utils
+                .moderation_config_log_channel(ctx, interaction, args)
+                .await
+        ),
+        "moderation config preset-add" => ensure_permission!(
+            Administrator,
+            handler
+                .utils
+                .moderation_preset_add(ctx, interaction, args)
+                .await
+        ),
+        "moderation config preset-remove" => ensure_permission!(
+            Administrator,
+            handler
+                .utils
+                .moderation_preset_remove(ctx, interaction, args)
+                .await
+        ),
+        "config export" => ensure_permission!(
+            Administrator,
+            handler.config.config_export(ctx, interaction).await
+        ),
+        "config import" => ensure_permission!(
+            Administrator,
+            handler.config.config_import(ctx, interaction, args).await
+        ),
+        "instance admin add" => ensure_owner!(
+            handler,
+            interaction,
+            handler
+                .permissions
+                .instance_admin_add(ctx, interaction, args)
+                .await
+        ),
+        "instance admin remove" => ensure_owner!(
+            handler,
+            interaction,
+            handler
+                .permissions
+                .instance_admin_remove(ctx, interaction, args, handler.owner_id)
+                .await
+        ),
+        "instance admin list" => ensure_owner!(
+            handler,
+            interaction,
+            handler
+                .permissions
+                .instance_admin_list(ctx, interaction)
+                .await
+        ),
+        "cases" => ensure_permission!(
+            ManageCases,
+            handler.cases.cases_command(ctx, interaction, args).await
+        ),
+        "case view" => ensure_permission!(
+            ManageCases,
+            handler.cases.case_view(ctx, interaction, args).await
+        ),
+        "case edit-reason" => ensure_permission!(
+            ManageCases,
+            handler.cases.case_edit_reason(ctx, interaction, args).await
+        ),
         _ => Ok(()),
     }
 }
@@ -183,19 +364,117 @@ pub async fn component_router(
         };
     }
 
-    let (ty, _args) = parse_custom_id(&interaction.data.custom_id)?;
+    let (ty, args) = parse_custom_id(&interaction.data.custom_id)?;
 
     debug!("received component with id {}", interaction.data.custom_id);
 
     use CustomIdType::*;
     match ty {
-        ListPermissions => ensure_permission!(
+        PermissionsPage => ensure_permission!(
+            ManagePermissions,
+            handler
+                .permissions
+                .permissions_list_component(ctx, interaction, args)
+                .await
+        ),
+        AuditLog => ensure_permission!(
+            ViewAuditLog,
+            handler.audit.audit_component(ctx, interaction, args).await
+        ),
+        ConfigImport => ensure_permission!(
+            Administrator,
+            handler
+                .config
+                .import_component(ctx, interaction, args)
+                .await
+        ),
+        // whoever triggered the preview may delete it too, so the handler decides
+        PreviewDelete => ensure_guild!(interaction, {
+            let manager =
+                missing_permission!(ctx, handler.permissions, interaction, ManagePreviews)
+                    .is_none();
+            handler
+                .previews
+                .delete_component(ctx, interaction, manager)
+                .await
+        }),
+    }
+}
+
+pub async fn autocomplete_router(
+    handler: &Handler,
+    ctx: &BotContext,
+    interaction: &AutocompleteInteraction,
+) -> Result<()> {
+    // suggestions can leak server data, so users without access just get none
+    macro_rules! ensure_permission {
+        ($permission: ident, $command: expr) => {
+            if missing_permission!(ctx, handler.permissions, interaction, $permission).is_some() {
+                interaction.create_autocomplete_response(ctx, |r| r).await?;
+                Ok(())
+            } else {
+                $command
+            }
+        };
+    }
+
+    let (path, focused) = decode::process_autocomplete(&interaction.data);
+    let (option, value) = match focused {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+
+    match (path.as_str(), option.as_str()) {
+        ("timeout" | "timeout-many", "duration") => ensure_permission!(
+            Timeout,
+            handler
+                .utils
+                .duration_autocomplete(ctx, interaction, &value, true)
+                .await
+        ),
+        ("ban", "duration") => ensure_permission!(
+            Ban,
+            handler
+                .utils
+                .duration_autocomplete(ctx, interaction, &value, false)
+                .await
+        ),
+        ("timeout" | "timeout-many" | "untimeout", "reason") => ensure_permission!(
+            Timeout,
+            handler
+                .utils
+                .reason_autocomplete(ctx, interaction, &value)
+                .await
+        ),
+        ("kick", "reason") => ensure_permission!(
+            Kick,
+            handler
+                .utils
+                .reason_autocomplete(ctx, interaction, &value)
+                .await
+        ),
+        ("ban" | "softban" | "unban", "reason") => ensure_permission!(
+            Ban,
+            handler
+                .utils
+                .reason_autocomplete(ctx, interaction, &value)
+                .await
+        ),
+        ("permissions set", "discord") => ensure_permission!(
             ManagePermissions,
             handler
                 .permissions
-                .permissions_list_component(ctx, interaction)
+                .discord_autocomplete(ctx, interaction, &value)
                 .await
         ),
+        ("moderation config preset-remove", "name") => ensure_permission!(
+            Administrator,
+            handler
+                .utils
+                .preset_autocomplete(ctx, interaction, &value)
+                .await
+        ),
+        _ => Ok(()),
     }
 }
 
@@ -3,19 +3,27 @@
 // You should have received a copy of the license along with this program
 // If not, see <https://www.gnu.org/licenses/#AGPL>
 
+use crate::modules::cases::{CaseAction, NewCase};
+use crate::modules::{Grant, PermissionType, PermissionsModule, PreviewsModule};
 use crate::prelude::*;
 use crate::utils::{BotContext, SqlId};
 use anyhow::Result;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use serenity::client::Cache;
-use serenity::http::{CacheHttp, Http};
-use serenity::model::id::GuildId;
+use serenity::http::{CacheHttp, Http, HttpError};
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::prelude::{Mentionable, SerenityError};
 use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
 use std::future::Future;
 use tokio::sync::broadcast;
 use tokio::time::sleep;
 
+// upper bound on how long the job worker sleeps, so newly scheduled jobs get picked up
+const JOB_POLL_INTERVAL: i64 = 30;
+// failed jobs are retried this many times before being dropped
+const JOB_MAX_ATTEMPTS: i32 = 5;
+
 #[derive(Clone, Debug)]
 pub enum TaskMessage {
     Kill,
@@ -121,3 +129,329 @@ pub async fn guild_cleanup(ctx: TaskContext) -> Result<()> {
 
     Ok(())
 }
+
+pub async fn permission_expiry(
+    ctx: TaskContext,
+    permissions: Arc<PermissionsModule>,
+) -> Result<()> {
+    let expired = sqlx::query(
+        "select id, guild_id, type, scope, deny, role_id, user_id from PermissionExpiries where expires_at <= now()",
+    )
+    .map(|row: PgRow| {
+        (
+            row.get::<i64, _>("id"),
+            row.get::<SqlId<GuildId>, _>("guild_id").0,
+            row.get::<PermissionType, _>("type"),
+            row.get::<Option<SqlId<ChannelId>>, _>("scope").map(|s| s.0),
+            row.get::<bool, _>("deny"),
+            row.get::<Option<SqlId<RoleId>>, _>("role_id").map(|s| s.0),
+            row.get::<Option<SqlId<UserId>>, _>("user_id").map(|s| s.0),
+        )
+    })
+    .fetch_all(&ctx.pool)
+    .await?;
+
+    for (id, guild, ty, scope, deny, role, user) in expired {
+        debug!("expiring {} grant {} in guild {}", ty.as_value(), id, guild);
+        let grant = if deny { Grant::Deny } else { Grant::Allow };
+        let result = permissions
+            .expire_grant(
+                &guild,
+                &ty,
+                scope,
+                grant,
+                role,
+                user,
+                &ctx.cache.current_user_id(),
+            )
+            .await;
+        // left in place to be retried on the next run
+        if let Err(e) = result {
+            error!("Error expiring permission grant {}: {:?}", id, e);
+            continue;
+        }
+        if let Err(e) = sqlx::query("delete from PermissionExpiries where id = $1")
+            .bind(id)
+            .execute(&ctx.pool)
+            .await
+        {
+            error!("Error removing permission expiry {}: {:?}", id, e);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn preview_cleanup(ctx: TaskContext, previews: Arc<PreviewsModule>) -> Result<()> {
+    debug!("starting preview cleanup");
+    previews.prune_preview_messages(&ctx.pool).await
+}
+
+/// One-shot jobs, persisted in the database so they survive restarts
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum Job {
+    Unban {
+        user: UserId,
+    },
+    Reminder {
+        channel: ChannelId,
+        user: UserId,
+        message: String,
+    },
+    RemoveRole {
+        user: UserId,
+        role: RoleId,
+    },
+}
+
+impl Job {
+    // discord errors meaning whatever the job acts on is gone, so retrying can't help
+    fn obsolete_by(&self, code: isize) -> bool {
+        match self {
+            // unknown ban
+            Job::Unban { .. } => code == 10026,
+            // unknown channel
+            Job::Reminder { .. } => code == 10003,
+            // unknown member, the role went with them, or unknown role
+            Job::RemoveRole { .. } => code == 10007 || code == 10011,
+        }
+    }
+}
+
+// the discord error code behind a failed request, if there is one
+fn discord_code(error: &anyhow::Error) -> Option<isize> {
+    match error.downcast_ref::<SerenityError>()? {
+        SerenityError::Http(e) => match &**e {
+            HttpError::UnsuccessfulRequest(r) => Some(r.error.code),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub async fn schedule_job(
+    pool: &PgPool,
+    guild: GuildId,
+    run_at: DateTime<Utc>,
+    job: &Job,
+) -> Result<()> {
+    sqlx::query("insert into ScheduledJobs (guild_id, run_at, job) values ($1, $2, $3)")
+        .bind(SqlId(guild))
+        .bind(run_at)
+        .bind(serde_json::to_string(job)?)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Pending jobs in a guild, skipping any that can't be read
+pub async fn pending_jobs(pool: &PgPool, guild: GuildId) -> Result<Vec<(i64, Job)>> {
+    Ok(
+        sqlx::query("select id, job from ScheduledJobs where guild_id = $1 order by run_at")
+            .bind(SqlId(guild))
+            .map(|row: PgRow| (row.get::<i64, _>("id"), row.get::<String, _>("job")))
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .filter_map(|(id, job)| Some((id, serde_json::from_str(&job).ok()?)))
+            .collect(),
+    )
+}
+
+/// Makes a pending job due now, so the worker runs it on its next pass
+pub async fn expedite_job(pool: &PgPool, id: i64) -> Result<()> {
+    sqlx::query("update ScheduledJobs set run_at = now() where id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Cancels pending jobs in a guild that are identical to `job`
+pub async fn cancel_job(pool: &PgPool, guild: GuildId, job: &Job) -> Result<()> {
+    sqlx::query("delete from ScheduledJobs where guild_id = $1 and job = $2")
+        .bind(SqlId(guild))
+        .bind(serde_json::to_string(job)?)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+async fn run_job(ctx: &TaskContext, guild: GuildId, job: &Job) -> Result<()> {
+    match *job {
+        Job::Unban { user } => {
+            guild.unban(ctx, user).await?;
+            NewCase {
+                guild_id: guild,
+                action: CaseAction::Unban,
+                target: user,
+                moderator: ctx.cache.current_user_id(),
+                reason: Some(s!("Temporary ban expired")),
+                duration: None,
+                anon: false,
+                shame: false,
+                dm: false,
+                dm_status: None,
+            }
+            .record(&ctx.pool)
+            .await?;
+        }
+        Job::Reminder {
+            channel,
+            user,
+            ref message,
+        } => {
+            channel
+                .send_message(ctx, |m| {
+                    m.content(user.mention())
+                        .allowed_mentions(|a| a.users([user]))
+                        .embed(|e| e.title("Reminder").description(message))
+                })
+                .await?;
+        }
+        Job::RemoveRole { user, role } => {
+            ctx.http
+                .remove_member_role(guild.0, user.0, role.0, Some("Temporary role expired"))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn run_due_jobs(ctx: &TaskContext) -> Result<()> {
+    let jobs = sqlx::query(
+        "select id, guild_id, job from ScheduledJobs where run_at <= now() order by run_at",
+    )
+    .map(|row: PgRow| {
+        (
+            row.get::<i64, _>("id"),
+            row.get::<SqlId<GuildId>, _>("guild_id").0,
+            row.get::<String, _>("job"),
+        )
+    })
+    .fetch_all(&ctx.pool)
+    .await?;
+
+    for (id, guild, job) in jobs {
+        debug!("running job {} in guild {}", id, guild);
+        let result = match serde_json::from_str::<Job>(&job) {
+            Ok(job) => match run_job(ctx, guild, &job).await {
+                Err(e) if discord_code(&e).is_some_and(|code| job.obsolete_by(code)) => {
+                    debug!("dropping job {}, its target is gone: {:?}", id, e);
+                    Ok(())
+                }
+                result => result,
+            },
+            Err(e) => Err(e.into()),
+        };
+
+        match result {
+            Ok(()) => {
+                sqlx::query("delete from ScheduledJobs where id = $1")
+                    .bind(id)
+                    .execute(&ctx.pool)
+                    .await?;
+            }
+            Err(e) => {
+                error!("Error in scheduled job {}: {:?}", id, e);
+                // back off and retry, unless we've already tried too many times
+                sqlx::query(
+                    "update ScheduledJobs set attempts = attempts + 1, run_at = $2 where id = $1",
+                )
+                .bind(id)
+                .bind(Utc::now() + Duration::minutes(1))
+                .execute(&ctx.pool)
+                .await?;
+                sqlx::query("delete from ScheduledJobs where id = $1 and attempts >= $2")
+                    .bind(id)
+                    .bind(JOB_MAX_ATTEMPTS)
+                    .execute(&ctx.pool)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn next_job_wait(ctx: &TaskContext) -> Result<std::time::Duration> {
+    let next = sqlx::query("select min(run_at) as next from ScheduledJobs")
+        .map(|row: PgRow| row.get::<Option<NaiveDateTime>, _>("next"))
+        .fetch_one(&ctx.pool)
+        .await?;
+
+    let max = Duration::seconds(JOB_POLL_INTERVAL);
+    let wait = match next {
+        Some(next) => (next - Utc::now().naive_utc()).clamp(Duration::zero(), max),
+        None => max,
+    };
+    Ok(wait.to_std()?)
+}
+
+pub async fn job_worker(ctx: TaskContext) {
+    let mut task_rx = ctx.task_tx.subscribe();
+    loop {
+        if let Err(e) = run_due_jobs(&ctx).await {
+            error!("Error in task Job Worker: {:?}", e);
+        }
+
+        let wait = next_job_wait(&ctx).await.unwrap_or_else(|e| {
+            error!("Error in task Job Worker: {:?}", e);
+            Duration::seconds(JOB_POLL_INTERVAL).to_std().unwrap()
+        });
+
+        tokio::select! {
+            _ = sleep(wait) => {},
+            resp = task_rx.recv() => match resp {
+                Ok(TaskMessage::Kill) => break,
+                Ok(TaskMessage::DestroyGuild(guild)) => {
+                    if let Err(e) = sqlx::query("delete from ScheduledJobs where guild_id = $1")
+                        .bind(SqlId(guild))
+                        .execute(&ctx.pool)
+                        .await
+                    {
+                        error!("Error cancelling jobs for guild {}: {:?}", guild, e);
+                    }
+                },
+                Err(e) => {
+                    error!("Error in broadcast receive in Job Worker: {:?}", e);
+                    break;
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn departed_member_drops_role_removal() {
+        let job = Job::RemoveRole {
+            user: UserId(1),
+            role: RoleId(2),
+        };
+        assert!(job.obsolete_by(10007));
+        assert!(job.obsolete_by(10011));
+        // missing permissions may get fixed, so that one is retried
+        assert!(!job.obsolete_by(50013));
+        assert!(!Job::Unban { user: UserId(1) }.obsolete_by(10007));
+    }
+
+    #[test]
+    fn jobs_round_trip() {
+        let job = Job::Reminder {
+            channel: ChannelId(3),
+            user: UserId(1),
+            message: s!("hi"),
+        };
+        let parsed = serde_json::from_str::<Job>(&serde_json::to_string(&job).unwrap()).unwrap();
+        assert!(matches!(
+            parsed,
+            Job::Reminder { channel, user, message }
+                if channel == ChannelId(3) && user == UserId(1) && message == "hi"
+        ));
+    }
+}
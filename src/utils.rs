@@ -3,21 +3,26 @@
 // You should have received a copy of the license along with this program
 // If not, see <https://www.gnu.org/licenses/#AGPL>
 
+use crate::custom_ids::{build_custom_id, CustomIdType};
 use crate::prelude::*;
 use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use serde::Deserializer;
+use serenity::builder::CreateComponents;
 use serenity::cache::Cache;
 use serenity::client::Context;
 use serenity::http::{CacheHttp, Http};
 use serenity::model::channel::{Channel, MessageReference};
 use serenity::model::guild::{Guild, Role};
 use serenity::model::id::{ChannelId, GuildId, RoleId};
+use serenity::model::prelude::component::ButtonStyle;
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
 use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
 use serenity::model::prelude::interaction::{InteractionResponseType, MessageFlags};
 use serenity::CacheAndHttp;
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -333,6 +338,33 @@ pub async fn defer_component<T: AsRef<Http>>(
     Ok(())
 }
 
+/// Adds Previous/Next buttons pointing at the neighbouring pages of `ty`
+pub fn create_page_buttons<'a>(
+    components: &'a mut CreateComponents,
+    ty: &CustomIdType,
+    page: u64,
+    has_next: bool,
+) -> &'a mut CreateComponents {
+    let page_id =
+        |page: u64| build_custom_id(ty, &Some(HashMap::from([(s!("page"), page.to_string())])));
+
+    components.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id(page_id(page.saturating_sub(1)))
+                .label("Previous")
+                .style(ButtonStyle::Secondary)
+                .disabled(page == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(page_id(page + 1))
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(!has_next)
+        })
+    })
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 pub enum OptionalOption<T> {
     Present(Option<T>),
@@ -363,25 +395,234 @@ pub fn highest_role(cached: &HashMap<RoleId, Role>, roles: &[RoleId]) -> i64 {
         .unwrap_or(0)
 }
 
-pub fn parse_duration(from: &str) -> Option<Duration> {
-    let mut duration = Duration::from_secs(0);
-    let mut num_array = String::new();
-    for char in from.chars() {
-        if char.is_ascii_digit() {
-            num_array.push(char)
-        } else {
-            let quantity = u64::from_str(&num_array).ok()?;
-            match char {
-                'd' => duration += Duration::from_secs(quantity * 60 * 60 * 24),
-                'h' => duration += Duration::from_secs(quantity * 60 * 60),
-                'm' => duration += Duration::from_secs(quantity * 60),
-                's' => duration += Duration::from_secs(quantity),
-                _ => return None,
+#[derive(Debug, PartialEq, Eq)]
+pub enum DurationError {
+    Empty,
+    Zero,
+    Overflow,
+    MissingUnit(String),
+    MissingQuantity(String),
+    UnknownUnit(String),
+    UnexpectedCharacter(char),
+    InvalidIso(String),
+    InvalidTime(String),
+    InPast,
+}
+
+impl Display for DurationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DurationError::Empty => f.write_str("Duration is empty"),
+            DurationError::Zero => f.write_str("Duration must be longer than zero"),
+            DurationError::Overflow => f.write_str("Duration is too large"),
+            DurationError::MissingUnit(n) => {
+                write!(f, "`{}` is missing a unit, try something like `{}m`", n, n)
+            }
+            DurationError::MissingQuantity(u) => write!(f, "`{}` is missing a number", u),
+            DurationError::UnknownUnit(u) => {
+                write!(f, "Unknown unit `{}`, use one of w, d, h, m or s", u)
+            }
+            DurationError::UnexpectedCharacter(c) => {
+                write!(f, "Unexpected character `{}` in duration", c)
+            }
+            DurationError::InvalidIso(s) => write!(f, "`{}` is not a valid ISO-8601 duration", s),
+            DurationError::InvalidTime(s) => write!(
+                f,
+                "`{}` is not a valid time, use the format `YYYY-MM-DD HH:MM` in UTC",
+                s
+            ),
+            DurationError::InPast => f.write_str("That time is in the past"),
+        }
+    }
+}
+
+fn unit_seconds(unit: &str) -> Option<u64> {
+    Some(match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 60 * 60 * 24,
+        "w" | "wk" | "wks" | "week" | "weeks" => 60 * 60 * 24 * 7,
+        _ => return None,
+    })
+}
+
+fn add_quantity(total: u64, quantity: &str, unit_size: u64) -> Result<u64, DurationError> {
+    u64::from_str(quantity)
+        .ok()
+        .and_then(|q| q.checked_mul(unit_size))
+        .and_then(|s| s.checked_add(total))
+        .ok_or(DurationError::Overflow)
+}
+
+// PnW, PnDTnHnMnS and so on. years and months are left out since their length varies
+fn parse_iso_duration(from: &str) -> Result<u64, DurationError> {
+    let invalid = || DurationError::InvalidIso(from.to_string());
+    let mut total = 0;
+    let mut quantity = String::new();
+    let mut in_time = false;
+    let mut any = false;
+    for char in from.to_lowercase().chars().skip(1) {
+        match char {
+            '0'..='9' => quantity.push(char),
+            't' if !in_time && quantity.is_empty() => in_time = true,
+            _ => {
+                if quantity.is_empty() {
+                    return Err(invalid());
+                }
+                let unit_size = match (in_time, char) {
+                    (false, 'w') => 60 * 60 * 24 * 7,
+                    (false, 'd') => 60 * 60 * 24,
+                    (true, 'h') => 60 * 60,
+                    (true, 'm') => 60,
+                    (true, 's') => 1,
+                    _ => return Err(invalid()),
+                };
+                total = add_quantity(total, &quantity, unit_size)?;
+                quantity.clear();
+                any = true;
+            }
+        }
+    }
+
+    if !quantity.is_empty() || !any {
+        return Err(invalid());
+    }
+
+    Ok(total)
+}
+
+// absolute end times are always read as utc
+fn parse_until(from: &str) -> Result<u64, DurationError> {
+    let end = [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dt%H:%M",
+        "%Y-%m-%dt%H:%M:%S",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(from, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(from, "%Y-%m-%d")
+            .ok()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| DurationError::InvalidTime(from.to_string()))?;
+
+    let seconds = (end - Utc::now().naive_utc()).num_seconds();
+    if seconds <= 0 {
+        return Err(DurationError::InPast);
+    }
+
+    Ok(seconds as u64)
+}
+
+/// Parses a duration such as `1d2h`, `2 hours 30 minutes`, `PT1H30M` or `until 2026-10-20 18:00`
+pub fn parse_duration(from: &str) -> Result<Duration, DurationError> {
+    let original = from.trim();
+    let from = original.to_lowercase();
+    if from.is_empty() {
+        return Err(DurationError::Empty);
+    }
+
+    let seconds = if let Some(time) = from.strip_prefix("until ") {
+        parse_until(time.trim())?
+    } else if from.starts_with('p') {
+        parse_iso_duration(original)?
+    } else {
+        let mut total = 0;
+        let mut quantity = String::new();
+        let mut unit = String::new();
+        let mut last_digit = false;
+        // an extra separator at the end flushes the last unit
+        for char in from.chars().chain(std::iter::once(' ')) {
+            if char.is_ascii_digit() || char.is_whitespace() || char == ',' {
+                if !unit.is_empty() {
+                    if quantity.is_empty() {
+                        if unit != "and" {
+                            return Err(DurationError::MissingQuantity(unit));
+                        }
+                    } else {
+                        let unit_size = unit_seconds(&unit)
+                            .ok_or_else(|| DurationError::UnknownUnit(unit.clone()))?;
+                        total = add_quantity(total, &quantity, unit_size)?;
+                        quantity.clear();
+                    }
+                    unit.clear();
+                }
+                if char.is_ascii_digit() {
+                    // digits split by a separator, like `1 2h`
+                    if !quantity.is_empty() && !last_digit {
+                        return Err(DurationError::MissingUnit(quantity));
+                    }
+                    quantity.push(char);
+                }
+            } else if char.is_alphabetic() {
+                unit.push(char);
+            } else {
+                return Err(DurationError::UnexpectedCharacter(char));
             }
+            last_digit = char.is_ascii_digit();
+        }
+
+        if !quantity.is_empty() {
+            return Err(DurationError::MissingUnit(quantity));
+        }
+
+        total
+    };
+
+    if seconds == 0 {
+        return Err(DurationError::Zero);
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Formats a duration like `1d 2h 3m 4s`, skipping empty units
+pub fn display_duration(duration: &Duration) -> String {
+    let mut remaining = duration.as_secs();
+    let mut parts = Vec::new();
+    for (unit, size) in [("d", 60 * 60 * 24), ("h", 60 * 60), ("m", 60), ("s", 1)] {
+        if remaining >= size {
+            parts.push(format!("{}{}", remaining / size, unit));
+            remaining %= size;
         }
     }
+    if parts.is_empty() {
+        s!("0s")
+    } else {
+        parts.join(" ")
+    }
+}
 
-    Some(duration)
+/// Formats a duration like `1 day 2 hours`, skipping empty units
+pub fn describe_duration(duration: &Duration) -> String {
+    let mut remaining = duration.as_secs();
+    let mut parts = Vec::new();
+    for (unit, size) in [
+        ("week", 60 * 60 * 24 * 7),
+        ("day", 60 * 60 * 24),
+        ("hour", 60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ] {
+        if remaining >= size {
+            let quantity = remaining / size;
+            parts.push(format!(
+                "{} {}{}",
+                quantity,
+                unit,
+                if quantity == 1 { "" } else { "s" }
+            ));
+            remaining %= size;
+        }
+    }
+    if parts.is_empty() {
+        s!("0 seconds")
+    } else {
+        parts.join(" ")
+    }
 }
 
 // we try our best to find a channel that everyone can see
@@ -413,3 +654,93 @@ pub fn link_guild(guild: &Guild, hint: &ChannelId) -> String {
             .link()
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(from: &str) -> Result<u64, DurationError> {
+        parse_duration(from).map(|d| d.as_secs())
+    }
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(seconds("30s"), Ok(30));
+        assert_eq!(seconds("5m"), Ok(5 * 60));
+        assert_eq!(seconds("2h"), Ok(2 * 60 * 60));
+        assert_eq!(seconds("1d"), Ok(24 * 60 * 60));
+        assert_eq!(seconds("1w"), Ok(7 * 24 * 60 * 60));
+        assert_eq!(seconds("3 weeks"), Ok(3 * 7 * 24 * 60 * 60));
+        assert_eq!(seconds("1 minute"), Ok(60));
+    }
+
+    #[test]
+    fn duration_combinations() {
+        assert_eq!(seconds("1d2h"), Ok(26 * 60 * 60));
+        assert_eq!(seconds("2 hours 30 minutes"), Ok(150 * 60));
+        assert_eq!(seconds("1h, 30m and 15s"), Ok(60 * 60 + 30 * 60 + 15));
+        assert_eq!(seconds("  1H30M  "), Ok(90 * 60));
+    }
+
+    #[test]
+    fn duration_iso() {
+        assert_eq!(seconds("PT1H30M"), Ok(90 * 60));
+        assert_eq!(seconds("P1DT12H"), Ok(36 * 60 * 60));
+        assert_eq!(seconds("P2W"), Ok(14 * 24 * 60 * 60));
+        assert_eq!(seconds("P1M"), Err(DurationError::InvalidIso(s!("P1M"))));
+        assert_eq!(seconds("PT"), Err(DurationError::InvalidIso(s!("PT"))));
+        assert_eq!(seconds("PT5"), Err(DurationError::InvalidIso(s!("PT5"))));
+    }
+
+    #[test]
+    fn duration_until() {
+        assert!(seconds("until 2999-01-01 00:00").is_ok());
+        assert!(seconds("until 2999-01-01").is_ok());
+        assert_eq!(seconds("until 2000-01-01"), Err(DurationError::InPast));
+        assert_eq!(
+            seconds("until tomorrow"),
+            Err(DurationError::InvalidTime(s!("tomorrow")))
+        );
+    }
+
+    #[test]
+    fn duration_overflow() {
+        assert_eq!(
+            seconds("99999999999999999999s"),
+            Err(DurationError::Overflow)
+        );
+        assert_eq!(seconds("40000000000000w"), Err(DurationError::Overflow));
+        assert_eq!(
+            seconds("18446744073709551615s 1s"),
+            Err(DurationError::Overflow)
+        );
+    }
+
+    #[test]
+    fn duration_malformed() {
+        assert_eq!(seconds(""), Err(DurationError::Empty));
+        assert_eq!(seconds("   "), Err(DurationError::Empty));
+        assert_eq!(seconds("0m"), Err(DurationError::Zero));
+        assert_eq!(seconds("10"), Err(DurationError::MissingUnit(s!("10"))));
+        assert_eq!(seconds("1 2h"), Err(DurationError::MissingUnit(s!("1"))));
+        assert_eq!(
+            seconds("hours"),
+            Err(DurationError::MissingQuantity(s!("hours")))
+        );
+        assert_eq!(
+            seconds("5 fortnights"),
+            Err(DurationError::UnknownUnit(s!("fortnights")))
+        );
+        assert_eq!(seconds("5m!"), Err(DurationError::UnexpectedCharacter('!')));
+        assert_eq!(seconds("-5m"), Err(DurationError::UnexpectedCharacter('-')));
+    }
+
+    #[test]
+    fn duration_display() {
+        assert_eq!(display_duration(&Duration::from_secs(0)), "0s");
+        assert_eq!(
+            display_duration(&Duration::from_secs(26 * 60 * 60 + 5)),
+            "1d 2h 5s"
+        );
+    }
+}